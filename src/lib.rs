@@ -1,16 +1,196 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// Without the default `std` feature the crate builds as `no_std` + `alloc`.
+// The consensus types, parsing, serialization and hashing are all available
+// either way, and `consensus_encode` / `consensus_decode` always take the
+// crate's own `io` traits (implemented for every `std::io` reader and writer
+// when `std` is on). APIs taking `HashMap` / `HashSet`, and the few that need
+// float intrinsics (`Amount::from_btc`, `MempoolEntry::from_json`,
+// `serialized_entropy`), are std-only. `cargo build --no-default-features`
+// is the compile check for that configuration.
+extern crate alloc;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use core::fmt;
+use core::ops::Deref;
+use core::str::FromStr;
+use io::{Read, Write};
 use serde::{Deserialize, Serialize};
-use std::fmt;
-use std::ops::Deref;
+use sha2::{Digest, Sha256};
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+// Just enough of `std::io` for the consensus encoders and decoders to stream
+// into a `Vec<u8>` or out of a `&[u8]` (or a caller's own impls). With `std`
+// every `std::io::Read` / `std::io::Write` implements these as well.
+pub mod io {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ErrorKind {
+        Interrupted,
+        UnexpectedEof,
+        Other,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Error(ErrorKind);
+
+    impl Error {
+        pub const fn new(kind: ErrorKind) -> Self {
+            Error(kind)
+        }
+
+        pub const fn kind(&self) -> ErrorKind {
+            self.0
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl From<std::io::Error> for Error {
+        fn from(err: std::io::Error) -> Self {
+            Error(match err.kind() {
+                std::io::ErrorKind::Interrupted => ErrorKind::Interrupted,
+                std::io::ErrorKind::UnexpectedEof => ErrorKind::UnexpectedEof,
+                _ => ErrorKind::Other,
+            })
+        }
+    }
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    pub trait Write {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+    }
+
+    pub trait Read {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+    }
+
+    #[cfg(feature = "std")]
+    impl<W: std::io::Write + ?Sized> Write for W {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            std::io::Write::write_all(self, buf).map_err(Error::from)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl<R: std::io::Read + ?Sized> Read for R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            std::io::Read::read(self, buf).map_err(Error::from)
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            (**self).write_all(buf)
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    impl Write for Vec<u8> {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            self.extend_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = buf.len().min(self.len());
+            let (head, tail) = self.split_at(n);
+            buf[..n].copy_from_slice(head);
+            *self = tail;
+            Ok(n)
+        }
+    }
+
+    // Discards everything written, like `std::io::Sink`
+    #[derive(Debug, Default)]
+    pub struct Sink;
+
+    pub const fn sink() -> Sink {
+        Sink
+    }
+
+    impl Write for Sink {
+        fn write_all(&mut self, _buf: &[u8]) -> Result<()> {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub struct CompactSize {
     pub value: u64,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+// `InsufficientBytes` carries how many bytes the failing parser needed and
+// how many it was given (both relative to the slice it was handed, so
+// `needed - got` is the shortfall). Code that matched the old unit variant
+// should match `BitcoinError::InsufficientBytes { .. }` instead.
+//
+// Serialized as an object tagged by `kind` (the snake_case variant name)
+// plus any context fields, e.g. {"kind":"insufficient_bytes","needed":36,"got":10}.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
 pub enum BitcoinError {
-    InsufficientBytes,
+    InsufficientBytes { needed: usize, got: usize },
     InvalidFormat,
+    InvalidOutputScript { index: usize },
+}
+
+impl fmt::Display for BitcoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BitcoinError::InsufficientBytes { needed, got } => {
+                write!(f, "insufficient bytes: needed {}, got {}", needed, got)
+            }
+            BitcoinError::InvalidFormat => write!(f, "invalid format"),
+            BitcoinError::InvalidOutputScript { index } => {
+                write!(f, "invalid script in output {}", index)
+            }
+        }
+    }
+}
+
+impl core::error::Error for BitcoinError {}
+
+impl From<u64> for CompactSize {
+    fn from(value: u64) -> Self {
+        CompactSize::new(value)
+    }
+}
+
+impl From<CompactSize> for u64 {
+    fn from(size: CompactSize) -> Self {
+        size.value
+    }
+}
+
+// Fails when the value does not fit the platform's usize (32-bit targets)
+impl TryFrom<CompactSize> for usize {
+    type Error = BitcoinError;
+
+    fn try_from(size: CompactSize) -> Result<Self, Self::Error> {
+        usize::try_from(size.value).map_err(|_| BitcoinError::InvalidFormat)
+    }
 }
 
 impl CompactSize {
@@ -19,63 +199,169 @@ impl CompactSize {
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
+        let (buf, len) = self.encode();
+        buf[..len].to_vec()
+    }
+
+    pub fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        let (buf, len) = self.encode();
+        writer.write_all(&buf[..len])?;
+        Ok(len)
+    }
+
+    pub fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, BitcoinError> {
+        let mut prefix = [0u8; 1];
+        read_exact(reader, &mut prefix)?;
+        Self::decode_after_prefix(prefix[0], reader)
+    }
+
+    // Reads whatever follows an already-consumed prefix byte, then defers to
+    // `from_bytes` so the canonical-encoding checks live in one place
+    fn decode_after_prefix<R: Read>(prefix: u8, reader: &mut R) -> Result<Self, BitcoinError> {
+        let len = match prefix {
+            0..=252 => 1,
+            0xFD => 3,
+            0xFE => 5,
+            0xFF => 9,
+        };
+        let mut buf = [0u8; 9];
+        buf[0] = prefix;
+        read_exact(reader, &mut buf[1..len])?;
+        Ok(CompactSize::from_bytes(&buf[..len])?.0)
+    }
+
+    // Number of bytes `to_bytes` would produce
+    pub fn encoded_len(&self) -> usize {
+        match self.value {
+            0..=252 => 1,
+            253..=65535 => 3,
+            65536..=4294967295 => 5,
+            _ => 9,
+        }
+    }
+
+    // Encodes into a fixed buffer, returning it with the number of bytes used
+    fn encode(&self) -> ([u8; 9], usize) {
+        let mut buf = [0u8; 9];
         match self.value {
-            0..=252 => vec![self.value as u8],
+            0..=252 => {
+                buf[0] = self.value as u8;
+                (buf, 1)
+            }
             253..=65535 => {
-                let mut bytes = vec![0xFD];
-                bytes.extend_from_slice(&(self.value as u16).to_le_bytes());
-                bytes
+                buf[0] = 0xFD;
+                buf[1..3].copy_from_slice(&(self.value as u16).to_le_bytes());
+                (buf, 3)
             }
             65536..=4294967295 => {
-                let mut bytes = vec![0xFE];
-                bytes.extend_from_slice(&(self.value as u32).to_le_bytes());
-                bytes
+                buf[0] = 0xFE;
+                buf[1..5].copy_from_slice(&(self.value as u32).to_le_bytes());
+                (buf, 5)
             }
             _ => {
-                let mut bytes = vec![0xFF];
-                bytes.extend_from_slice(&self.value.to_le_bytes());
-                bytes
+                buf[0] = 0xFF;
+                buf[1..9].copy_from_slice(&self.value.to_le_bytes());
+                (buf, 9)
             }
         }
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
         if bytes.is_empty() {
-            return Err(BitcoinError::InsufficientBytes);
+            return Err(BitcoinError::InsufficientBytes { needed: 1, got: 0 });
         }
 
         match bytes[0] {
             0..=252 => Ok((CompactSize::new(bytes[0] as u64), 1)),
             0xFD => {
                 if bytes.len() < 3 {
-                    return Err(BitcoinError::InsufficientBytes);
+                    return Err(BitcoinError::InsufficientBytes {
+                        needed: 3,
+                        got: bytes.len(),
+                    });
                 }
                 let value = u16::from_le_bytes([bytes[1], bytes[2]]) as u64;
+                // Consensus requires the shortest encoding
+                if value <= 252 {
+                    return Err(BitcoinError::InvalidFormat);
+                }
                 Ok((CompactSize::new(value), 3))
             }
             0xFE => {
                 if bytes.len() < 5 {
-                    return Err(BitcoinError::InsufficientBytes);
+                    return Err(BitcoinError::InsufficientBytes {
+                        needed: 5,
+                        got: bytes.len(),
+                    });
                 }
                 let value = u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]) as u64;
+                if value <= 0xFFFF {
+                    return Err(BitcoinError::InvalidFormat);
+                }
                 Ok((CompactSize::new(value), 5))
             }
             0xFF => {
                 if bytes.len() < 9 {
-                    return Err(BitcoinError::InsufficientBytes);
+                    return Err(BitcoinError::InsufficientBytes {
+                        needed: 9,
+                        got: bytes.len(),
+                    });
                 }
                 let value = u64::from_le_bytes([
                     bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7], bytes[8],
                 ]);
+                if value <= 0xFFFF_FFFF {
+                    return Err(BitcoinError::InvalidFormat);
+                }
                 Ok((CompactSize::new(value), 9))
             }
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+// Byte order: the inner array is in internal (wire) order, exactly as the
+// hash comes out of SHA-256d. Explorers, RPC and `bitcoin-cli` show the
+// reverse of that.
+//
+// - `Display` / `FromStr`: reversed display order (what explorers show)
+// - `from_raw_bytes` / `to_raw_bytes` / `.0`: internal order, no reversal
+// - serde: internal-order hex, no reversal
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
 pub struct Txid(pub [u8; 32]);
 
+impl Txid {
+    // Internal byte order, no reversal
+    pub fn from_raw_bytes(bytes: [u8; 32]) -> Self {
+        Txid(bytes)
+    }
+
+    // Internal byte order, no reversal
+    pub fn to_raw_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+}
+
+// Reversed display order, matching block explorers
+impl fmt::Display for Txid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bytes = self.0;
+        bytes.reverse();
+        write!(f, "{}", hex::encode(bytes))
+    }
+}
+
+// Parses a txid as shown by explorers, reversing it into internal order
+impl FromStr for Txid {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s).map_err(|_| BitcoinError::InvalidFormat)?;
+        let mut txid: [u8; 32] = bytes.try_into().map_err(|_| BitcoinError::InvalidFormat)?;
+        txid.reverse();
+        Ok(Txid(txid))
+    }
+}
+
 impl Serialize for Txid {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -104,13 +390,15 @@ impl<'de> Deserialize<'de> for Txid {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, Serialize, Deserialize)]
 pub struct OutPoint {
     pub txid: Txid,
     pub vout: u32,
 }
 
 impl OutPoint {
+    // `txid` is in internal byte order; use `Txid::from_str` first when
+    // starting from an explorer-style hex string
     pub fn new(txid: [u8; 32], vout: u32) -> Self {
         OutPoint {
             txid: Txid(txid),
@@ -118,16 +406,39 @@ impl OutPoint {
         }
     }
 
+    // The prevout of a coinbase input: all-zero txid, vout 0xFFFFFFFF
+    pub fn null() -> Self {
+        OutPoint::new([0u8; 32], 0xFFFFFFFF)
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == OutPoint::null()
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&self.txid.0);
-        bytes.extend_from_slice(&self.vout.to_le_bytes());
-        bytes
+        encode_to_vec(|bytes| self.consensus_encode(bytes))
+    }
+
+    pub fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        writer.write_all(&self.txid.0)?;
+        writer.write_all(&self.vout.to_le_bytes())?;
+        Ok(36)
+    }
+
+    pub fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, BitcoinError> {
+        let mut txid = [0u8; 32];
+        read_exact(reader, &mut txid)?;
+        let mut vout = [0u8; 4];
+        read_exact(reader, &mut vout)?;
+        Ok(OutPoint::new(txid, u32::from_le_bytes(vout)))
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
         if bytes.len() < 36 {
-            return Err(BitcoinError::InsufficientBytes);
+            return Err(BitcoinError::InsufficientBytes {
+                needed: 36,
+                got: bytes.len(),
+            });
         }
 
         let mut txid = [0u8; 32];
@@ -150,21 +461,455 @@ impl Script {
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
-        let length = CompactSize::new(self.bytes.len() as u64);
-        let mut result = length.to_bytes();
-        result.extend_from_slice(&self.bytes);
-        result
+        encode_to_vec(|bytes| self.consensus_encode(bytes))
+    }
+
+    pub fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        let length_bytes = CompactSize::new(self.bytes.len() as u64).consensus_encode(writer)?;
+        writer.write_all(&self.bytes)?;
+        Ok(length_bytes + self.bytes.len())
+    }
+
+    pub fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, BitcoinError> {
+        let length = CompactSize::consensus_decode(reader)?;
+        Ok(Script::new(read_vec(reader, length.value)?))
     }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
         let (length, length_bytes) = CompactSize::from_bytes(bytes)?;
-        let script_length = length.value as usize;
+        let script_length = usize::try_from(length)?;
+
+        let script_bytes = slice_at(bytes, length_bytes, script_length)?.to_vec();
+        Ok((Script::new(script_bytes), length_bytes + script_length))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ScriptType {
+    P2PK,
+    P2PKH,
+    P2SH,
+    P2WPKH,
+    P2WSH,
+    P2TR,
+    Multisig,
+    OpReturn,
+    NonStandard,
+}
+
+impl Script {
+    // BIP141 witness program: a version opcode followed by a single 2-40
+    // byte push
+    pub fn is_witness_program(&self) -> bool {
+        let b = &self.bytes;
+        (4..=42).contains(&b.len())
+            && (b[0] == 0x00 || (0x51..=0x60).contains(&b[0]))
+            && b[1] as usize == b.len() - 2
+    }
 
-        if bytes.len() < length_bytes + script_length {
-            return Err(BitcoinError::InsufficientBytes);
+    pub fn classify(&self) -> ScriptType {
+        let b = &self.bytes;
+        match b.len() {
+            25 if b[0] == 0x76
+                && b[1] == 0xA9
+                && b[2] == 0x14
+                && b[23] == 0x88
+                && b[24] == 0xAC =>
+            {
+                ScriptType::P2PKH
+            }
+            23 if b[0] == 0xA9 && b[1] == 0x14 && b[22] == 0x87 => ScriptType::P2SH,
+            22 if b[0] == 0x00 && b[1] == 0x14 => ScriptType::P2WPKH,
+            34 if b[0] == 0x00 && b[1] == 0x20 => ScriptType::P2WSH,
+            34 if b[0] == 0x51 && b[1] == 0x20 => ScriptType::P2TR,
+            35 if b[0] == 0x21 && b[34] == 0xAC => ScriptType::P2PK,
+            67 if b[0] == 0x41 && b[66] == 0xAC => ScriptType::P2PK,
+            _ if b.first() == Some(&0x6A) => ScriptType::OpReturn,
+            // OP_m <pubkeys...> OP_n OP_CHECKMULTISIG
+            3.. if (0x51..=0x60).contains(&b[0])
+                && (0x51..=0x60).contains(&b[b.len() - 2])
+                && b[b.len() - 1] == 0xAE =>
+            {
+                ScriptType::Multisig
+            }
+            _ => ScriptType::NonStandard,
         }
+    }
+}
 
-        let script_bytes = bytes[length_bytes..length_bytes + script_length].to_vec();
-        Ok((Script::new(script_bytes), length_bytes + script_length))
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Instruction<'a> {
+    // A data push together with the opcode that introduced it
+    Push { opcode: u8, data: &'a [u8] },
+    Op(u8),
+}
+
+pub struct Instructions<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<Instruction<'a>, BitcoinError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let opcode = *self.bytes.get(self.pos)?;
+        self.pos += 1;
+
+        // Width of the explicit length prefix for OP_PUSHDATA1/2/4
+        let (length, prefix) = match opcode {
+            0x00..=0x4B => (opcode as usize, 0),
+            0x4C..=0x4E => {
+                let prefix = 1 << (opcode - 0x4C);
+                if self.bytes.len() < self.pos + prefix {
+                    self.pos = self.bytes.len();
+                    return Some(Err(BitcoinError::InvalidFormat));
+                }
+                let mut length = [0u8; 4];
+                length[..prefix].copy_from_slice(&self.bytes[self.pos..self.pos + prefix]);
+                (u32::from_le_bytes(length) as usize, prefix)
+            }
+            _ => return Some(Ok(Instruction::Op(opcode))),
+        };
+        self.pos += prefix;
+
+        if self.bytes.len() - self.pos < length {
+            self.pos = self.bytes.len();
+            return Some(Err(BitcoinError::InvalidFormat));
+        }
+        let data = &self.bytes[self.pos..self.pos + length];
+        self.pos += length;
+        Some(Ok(Instruction::Push { opcode, data }))
+    }
+}
+
+// Names of the non-push opcodes OP_1NEGATE (0x4F) through OP_CHECKSIGADD (0xBA)
+const OPCODE_NAMES: [&str; 108] = [
+    "OP_1NEGATE",
+    "OP_RESERVED",
+    "OP_1",
+    "OP_2",
+    "OP_3",
+    "OP_4",
+    "OP_5",
+    "OP_6",
+    "OP_7",
+    "OP_8",
+    "OP_9",
+    "OP_10",
+    "OP_11",
+    "OP_12",
+    "OP_13",
+    "OP_14",
+    "OP_15",
+    "OP_16",
+    "OP_NOP",
+    "OP_VER",
+    "OP_IF",
+    "OP_NOTIF",
+    "OP_VERIF",
+    "OP_VERNOTIF",
+    "OP_ELSE",
+    "OP_ENDIF",
+    "OP_VERIFY",
+    "OP_RETURN",
+    "OP_TOALTSTACK",
+    "OP_FROMALTSTACK",
+    "OP_2DROP",
+    "OP_2DUP",
+    "OP_3DUP",
+    "OP_2OVER",
+    "OP_2ROT",
+    "OP_2SWAP",
+    "OP_IFDUP",
+    "OP_DEPTH",
+    "OP_DROP",
+    "OP_DUP",
+    "OP_NIP",
+    "OP_OVER",
+    "OP_PICK",
+    "OP_ROLL",
+    "OP_ROT",
+    "OP_SWAP",
+    "OP_TUCK",
+    "OP_CAT",
+    "OP_SUBSTR",
+    "OP_LEFT",
+    "OP_RIGHT",
+    "OP_SIZE",
+    "OP_INVERT",
+    "OP_AND",
+    "OP_OR",
+    "OP_XOR",
+    "OP_EQUAL",
+    "OP_EQUALVERIFY",
+    "OP_RESERVED1",
+    "OP_RESERVED2",
+    "OP_1ADD",
+    "OP_1SUB",
+    "OP_2MUL",
+    "OP_2DIV",
+    "OP_NEGATE",
+    "OP_ABS",
+    "OP_NOT",
+    "OP_0NOTEQUAL",
+    "OP_ADD",
+    "OP_SUB",
+    "OP_MUL",
+    "OP_DIV",
+    "OP_MOD",
+    "OP_LSHIFT",
+    "OP_RSHIFT",
+    "OP_BOOLAND",
+    "OP_BOOLOR",
+    "OP_NUMEQUAL",
+    "OP_NUMEQUALVERIFY",
+    "OP_NUMNOTEQUAL",
+    "OP_LESSTHAN",
+    "OP_GREATERTHAN",
+    "OP_LESSTHANOREQUAL",
+    "OP_GREATERTHANOREQUAL",
+    "OP_MIN",
+    "OP_MAX",
+    "OP_WITHIN",
+    "OP_RIPEMD160",
+    "OP_SHA1",
+    "OP_SHA256",
+    "OP_HASH160",
+    "OP_HASH256",
+    "OP_CODESEPARATOR",
+    "OP_CHECKSIG",
+    "OP_CHECKSIGVERIFY",
+    "OP_CHECKMULTISIG",
+    "OP_CHECKMULTISIGVERIFY",
+    "OP_NOP1",
+    "OP_CHECKLOCKTIMEVERIFY",
+    "OP_CHECKSEQUENCEVERIFY",
+    "OP_NOP4",
+    "OP_NOP5",
+    "OP_NOP6",
+    "OP_NOP7",
+    "OP_NOP8",
+    "OP_NOP9",
+    "OP_NOP10",
+    "OP_CHECKSIGADD",
+];
+
+// Known OP_RETURN payload prefixes and the protocol they mark
+const OP_RETURN_PROTOCOLS: [(&[u8], &str); 6] = [
+    (b"DOCPROOF", "DocProof"),
+    (b"ASCRIBE", "Ascribe"),
+    (b"omni", "Omni"),
+    (b"OA", "OpenAssets"),
+    (b"EW", "EternityWall"),
+    (b"id", "Blockstack"),
+];
+
+impl Script {
+    // <pubkey> OP_CHECKSIG
+    pub fn p2pk(pubkey: &[u8]) -> Script {
+        let mut script = Script::new(Vec::new());
+        script.push_data(pubkey);
+        script.bytes.push(0xAC);
+        script
+    }
+
+    // OP_DUP OP_HASH160 <hash> OP_EQUALVERIFY OP_CHECKSIG
+    pub fn p2pkh(pubkey_hash: [u8; 20]) -> Script {
+        let mut script = Script::new(vec![0x76, 0xA9]);
+        script.push_data(&pubkey_hash);
+        script.bytes.extend_from_slice(&[0x88, 0xAC]);
+        script
+    }
+
+    // Version 0 witness program: OP_0 <hash>
+    pub fn p2wpkh(pubkey_hash: [u8; 20]) -> Script {
+        let mut script = Script::new(vec![0x00]);
+        script.push_data(&pubkey_hash);
+        script
+    }
+
+    // Appends `data` with the shortest push opcode for its length: a direct
+    // push up to 75 bytes, then OP_PUSHDATA1/2/4. Single small values are
+    // pushed as data, not as OP_1-OP_16.
+    pub fn push_data(&mut self, data: &[u8]) {
+        match data.len() {
+            0..=0x4B => self.bytes.push(data.len() as u8),
+            0x4C..=0xFF => self.bytes.extend_from_slice(&[0x4C, data.len() as u8]),
+            0x100..=0xFFFF => {
+                self.bytes.push(0x4D);
+                self.bytes
+                    .extend_from_slice(&(data.len() as u16).to_le_bytes());
+            }
+            _ => {
+                self.bytes.push(0x4E);
+                self.bytes
+                    .extend_from_slice(&(data.len() as u32).to_le_bytes());
+            }
+        }
+        self.bytes.extend_from_slice(data);
+    }
+
+    pub fn parse_p2pk(&self) -> Option<Vec<u8>> {
+        match self.classify() {
+            ScriptType::P2PK => Some(self.bytes[1..self.bytes.len() - 1].to_vec()),
+            _ => None,
+        }
+    }
+
+    // Label of the data protocol whose prefix starts the OP_RETURN payload
+    pub fn op_return_protocol(&self) -> Option<&str> {
+        if self.first() != Some(&0x6A) {
+            return None;
+        }
+        let mut payload = Instructions {
+            bytes: &self.bytes[1..],
+            pos: 0,
+        };
+        let Some(Ok(Instruction::Push { data: payload, .. })) = payload.next() else {
+            return None;
+        };
+        OP_RETURN_PROTOCOLS
+            .iter()
+            .find(|(prefix, _)| payload.starts_with(prefix))
+            .map(|(_, label)| *label)
+    }
+
+    // Outputs used to carry data rather than value: OP_RETURN with pushed
+    // data, or bare multisig whose "pubkeys" aren't valid key encodings
+    // (the classic data-stuffing pattern)
+    pub fn is_data_carrier(&self) -> bool {
+        match self.classify() {
+            ScriptType::OpReturn => Instructions {
+                bytes: &self.bytes[1..],
+                pos: 0,
+            }
+            .any(|instruction| {
+                matches!(instruction, Ok(Instruction::Push { data, .. }) if !data.is_empty())
+            }),
+            ScriptType::Multisig => self.instructions().any(|instruction| match instruction {
+                Ok(Instruction::Push { data, .. }) => !is_pubkey_encoding(data),
+                Ok(Instruction::Op(_)) => false,
+                Err(_) => true,
+            }),
+            _ => false,
+        }
+    }
+
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions {
+            bytes: &self.bytes,
+            pos: 0,
+        }
+    }
+
+    // Legacy sigop counting. With `accurate`, a CHECKMULTISIG preceded by
+    // OP_1..OP_16 counts as that many keys instead of the worst-case 20.
+    // Counting stops at the first malformed push, as in Bitcoin Core.
+    pub fn sigop_count(&self, accurate: bool) -> usize {
+        let mut count = 0;
+        let mut last_opcode = None;
+        for instruction in self.instructions() {
+            let Ok(instruction) = instruction else {
+                break;
+            };
+            match instruction {
+                Instruction::Op(0xAC | 0xAD) => count += 1,
+                Instruction::Op(0xAE | 0xAF) => match last_opcode {
+                    Some(n @ 0x51..=0x60) if accurate => count += (n - 0x50) as usize,
+                    _ => count += 20,
+                },
+                _ => {}
+            }
+            last_opcode = match instruction {
+                Instruction::Op(opcode) => Some(opcode),
+                Instruction::Push { opcode, .. } => Some(opcode),
+            };
+        }
+        count
+    }
+
+    // Checks every push is complete, i.e. no push runs past the end
+    pub fn validate_pushes(&self) -> Result<(), BitcoinError> {
+        for instruction in self.instructions() {
+            instruction?;
+        }
+        Ok(())
+    }
+
+    // Runs a push-only script (such as a BIP62-conforming scriptSig) and
+    // returns the resulting stack, bottom first. Any non-push opcode fails.
+    pub fn execute_push_only(&self) -> Result<Vec<Vec<u8>>, BitcoinError> {
+        let mut stack = Vec::new();
+        for instruction in self.instructions() {
+            match instruction? {
+                Instruction::Push { data, .. } => stack.push(data.to_vec()),
+                // OP_1NEGATE
+                Instruction::Op(0x4F) => stack.push(vec![0x81]),
+                // OP_1 through OP_16
+                Instruction::Op(n @ 0x51..=0x60) => stack.push(vec![n - 0x50]),
+                Instruction::Op(_) => return Err(BitcoinError::InvalidFormat),
+            }
+        }
+        Ok(stack)
+    }
+
+    // ASM in the style of `decodescript`: pushed data as hex, OP_PUSHDATA1/2/4
+    // kept visible so non-minimal encodings stand out, unassigned opcodes as
+    // OP_UNKNOWN(0xNN). A truncated push is an error.
+    pub fn disassemble(&self) -> Result<String, BitcoinError> {
+        let mut words = Vec::new();
+        for instruction in self.instructions() {
+            words.push(match instruction? {
+                Instruction::Push { opcode: 0x00, .. } => "OP_0".to_string(),
+                Instruction::Push {
+                    opcode: opcode @ 0x4C..=0x4E,
+                    data,
+                } => format!("OP_PUSHDATA{} {}", 1 << (opcode - 0x4C), hex::encode(data)),
+                Instruction::Push { data, .. } => hex::encode(data),
+                Instruction::Op(opcode) => match opcode.checked_sub(0x4F) {
+                    Some(index) if (index as usize) < OPCODE_NAMES.len() => {
+                        OPCODE_NAMES[index as usize].to_string()
+                    }
+                    _ => format!("OP_UNKNOWN(0x{:02x})", opcode),
+                },
+            });
+        }
+        Ok(words.join(" "))
+    }
+
+    // BIP62 minimal pushes: every push uses the shortest opcode for its data,
+    // including OP_0, OP_1NEGATE and OP_1-OP_16 for single small values.
+    // Malformed scripts are not minimal.
+    pub fn has_minimal_pushes(&self) -> bool {
+        self.instructions().all(|instruction| match instruction {
+            Ok(Instruction::Push { opcode, data }) => match data {
+                [] => opcode == 0x00,
+                [0x01..=0x10] | [0x81] => false,
+                _ if data.len() <= 0x4B => opcode as usize == data.len(),
+                _ if data.len() <= 0xFF => opcode == 0x4C,
+                _ if data.len() <= 0xFFFF => opcode == 0x4D,
+                _ => opcode == 0x4E,
+            },
+            Ok(Instruction::Op(_)) => true,
+            Err(_) => false,
+        })
+    }
+
+    // The data of the final push, e.g. the redeem script of a P2SH scriptSig
+    pub fn last_push(&self) -> Option<Vec<u8>> {
+        match self.instructions().last()? {
+            Ok(Instruction::Push { data, .. }) => Some(data.to_vec()),
+            _ => None,
+        }
+    }
+}
+
+fn is_pubkey_encoding(data: &[u8]) -> bool {
+    match data.len() {
+        33 => data[0] == 0x02 || data[0] == 0x03,
+        65 => data[0] == 0x04,
+        _ => false,
     }
 }
 
@@ -175,11 +920,63 @@ impl Deref for Script {
     }
 }
 
+// An input's nSequence. The relative-timelock readings follow BIP68, which
+// only applies to transactions with version 2 or higher.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Sequence(u32);
+
+// BIP68: set means no relative timelock; set type flag means 512-second units
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000FFFF;
+
+impl Sequence {
+    pub const fn from_u32(sequence: u32) -> Self {
+        Sequence(sequence)
+    }
+
+    pub const fn to_u32(self) -> u32 {
+        self.0
+    }
+
+    // 0xFFFFFFFF: the input opts out of the transaction's lock_time
+    pub fn is_final(self) -> bool {
+        self.0 == SEQUENCE_FINAL
+    }
+
+    // BIP125: anything below 0xFFFFFFFE signals replaceability
+    pub fn is_rbf_signaling(self) -> bool {
+        self.0 < 0xFFFFFFFE
+    }
+
+    pub fn is_relative_timelock(self) -> bool {
+        self.0 & SEQUENCE_LOCKTIME_DISABLE_FLAG == 0
+    }
+
+    // Relative lock in blocks, when this is a block-based relative timelock
+    pub fn to_relative_lock_blocks(self) -> Option<u16> {
+        if !self.is_relative_timelock() || self.0 & SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+            return None;
+        }
+        Some((self.0 & SEQUENCE_LOCKTIME_MASK) as u16)
+    }
+
+    // Relative lock in seconds (512-second granularity), when this is a
+    // time-based relative timelock
+    pub fn to_relative_lock_time(self) -> Option<u32> {
+        if !self.is_relative_timelock() || self.0 & SEQUENCE_LOCKTIME_TYPE_FLAG == 0 {
+            return None;
+        }
+        Some((self.0 & SEQUENCE_LOCKTIME_MASK) * 512)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct TransactionInput {
     pub previous_output: OutPoint,
     pub script_sig: Script,
-    pub sequence: u32,
+    pub sequence: Sequence,
 }
 
 impl TransactionInput {
@@ -187,16 +984,31 @@ impl TransactionInput {
         TransactionInput {
             previous_output,
             script_sig,
-            sequence,
+            sequence: Sequence::from_u32(sequence),
         }
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&self.previous_output.to_bytes());
-        bytes.extend_from_slice(&self.script_sig.to_bytes());
-        bytes.extend_from_slice(&self.sequence.to_le_bytes());
-        bytes
+        encode_to_vec(|bytes| self.consensus_encode(bytes))
+    }
+
+    pub fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        let mut written = self.previous_output.consensus_encode(writer)?;
+        written += self.script_sig.consensus_encode(writer)?;
+        writer.write_all(&self.sequence.to_u32().to_le_bytes())?;
+        Ok(written + 4)
+    }
+
+    pub fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, BitcoinError> {
+        let previous_output = OutPoint::consensus_decode(reader)?;
+        let script_sig = Script::consensus_decode(reader)?;
+        let mut sequence = [0u8; 4];
+        read_exact(reader, &mut sequence)?;
+        Ok(TransactionInput::new(
+            previous_output,
+            script_sig,
+            u32::from_le_bytes(sequence),
+        ))
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
@@ -212,7 +1024,10 @@ impl TransactionInput {
 
         // Parse sequence
         if bytes.len() < consumed + 4 {
-            return Err(BitcoinError::InsufficientBytes);
+            return Err(BitcoinError::InsufficientBytes {
+                needed: consumed + 4,
+                got: bytes.len(),
+            });
         }
 
         let sequence = u32::from_le_bytes([
@@ -228,84 +1043,2214 @@ impl TransactionInput {
             consumed,
         ))
     }
-}
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
-pub struct BitcoinTransaction {
-    pub version: u32,
-    pub inputs: Vec<TransactionInput>,
-    pub lock_time: u32,
-}
+    // Fast path for native segwit inputs whose scriptSig is known to be empty:
+    // the single length byte must be 0x00, so an input is always 41 bytes
+    pub fn from_bytes_no_scriptsig(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        if bytes.len() < 41 {
+            return Err(BitcoinError::InsufficientBytes {
+                needed: 41,
+                got: bytes.len(),
+            });
+        }
+        if bytes[36] != 0x00 {
+            return Err(BitcoinError::InvalidFormat);
+        }
 
-impl BitcoinTransaction {
-    pub fn new(version: u32, inputs: Vec<TransactionInput>, lock_time: u32) -> Self {
-        BitcoinTransaction {
-            version,
-            inputs,
-            lock_time,
+        let (previous_output, _) = OutPoint::from_bytes(bytes)?;
+        let sequence = u32::from_le_bytes([bytes[37], bytes[38], bytes[39], bytes[40]]);
+
+        Ok((
+            TransactionInput::new(previous_output, Script::new(Vec::new()), sequence),
+            41,
+        ))
+    }
+
+    // The redeem script of a P2SH spend: the final push of a push-only
+    // scriptSig, used as the script code when computing a legacy sighash
+    pub fn p2sh_redeem_script(&self) -> Option<Script> {
+        let mut stack = self.script_sig.execute_push_only().ok()?;
+        stack
+            .pop()
+            .filter(|redeem_script| !redeem_script.is_empty())
+            .map(Script::new)
+    }
+
+    // Relay policy for legacy scriptSigs: push-only, with minimal pushes
+    pub fn has_canonical_scriptsig(&self) -> bool {
+        self.script_sig.execute_push_only().is_ok() && self.script_sig.has_minimal_pushes()
+    }
+
+    // Every unlocking push as one flat stack: scriptSig pushes first, then
+    // witness items. Non-push opcodes in the scriptSig are skipped.
+    pub fn full_unlocking_data(&self, witness: Option<&Witness>) -> Vec<Vec<u8>> {
+        let mut stack: Vec<Vec<u8>> = self
+            .script_sig
+            .instructions()
+            .map_while(Result::ok)
+            .filter_map(|instruction| match instruction {
+                Instruction::Push { data, .. } => Some(data.to_vec()),
+                Instruction::Op(_) => None,
+            })
+            .collect();
+        if let Some(witness) = witness {
+            stack.extend(witness.items.iter().cloned());
         }
+        stack
     }
+}
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct TransactionOutput {
+    pub value: Amount,
+    pub script_pubkey: Script,
+}
 
-        // Version (4 bytes LE)
-        bytes.extend_from_slice(&self.version.to_le_bytes());
+pub type TxOut = TransactionOutput;
 
-        // Input count (CompactSize)
-        let input_count = CompactSize::new(self.inputs.len() as u64);
-        bytes.extend_from_slice(&input_count.to_bytes());
+// 21 million BTC in satoshis
+pub const MAX_MONEY: u64 = 21_000_000 * 100_000_000;
 
-        // Each input
-        for input in &self.inputs {
-            bytes.extend_from_slice(&input.to_bytes());
-        }
+const SATS_PER_BTC: u64 = 100_000_000;
 
-        // Lock time (4 bytes LE)
-        bytes.extend_from_slice(&self.lock_time.to_le_bytes());
+// A value in satoshis
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Amount(u64);
 
-        bytes
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+
+    pub const fn from_sat(sats: u64) -> Self {
+        Amount(sats)
     }
 
-    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        let mut consumed = 0;
+    // Rejects anything above the 21 million BTC supply
+    pub fn from_sat_checked(sats: u64) -> Result<Self, BitcoinError> {
+        if sats > MAX_MONEY {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        Ok(Amount(sats))
+    }
 
-        // Parse version
-        if bytes.len() < 4 {
-            return Err(BitcoinError::InsufficientBytes);
+    // Rounds to the nearest satoshi, so values like 0.1 that have no exact
+    // binary representation land where expected. Negative, non-finite and
+    // above-supply values are rejected.
+    #[cfg(feature = "std")]
+    pub fn from_btc(btc: f64) -> Result<Self, BitcoinError> {
+        if !btc.is_finite() || btc < 0.0 {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        let sats = (btc * SATS_PER_BTC as f64).round();
+        if sats > MAX_MONEY as f64 {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        Ok(Amount(sats as u64))
+    }
+
+    pub const fn to_sat(self) -> u64 {
+        self.0
+    }
+
+    pub fn to_btc(self) -> f64 {
+        self.0 as f64 / SATS_PER_BTC as f64
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+}
+
+// Bitcoin Core's default -dustrelayfee, in sat/kvB
+pub const DUST_RELAY_TX_FEE: u64 = 3_000;
+
+// Largest OP_RETURN payload relayed by default (-datacarriersize)
+pub const MAX_OP_RETURN_RELAY: usize = 80;
+
+impl TransactionOutput {
+    // `value` is in satoshis
+    pub fn new(value: u64, script_pubkey: Script) -> Self {
+        TransactionOutput {
+            value: Amount::from_sat(value),
+            script_pubkey,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode_to_vec(|bytes| self.consensus_encode(bytes))
+    }
+
+    pub fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        writer.write_all(&self.value.to_sat().to_le_bytes())?;
+        Ok(8 + self.script_pubkey.consensus_encode(writer)?)
+    }
+
+    pub fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, BitcoinError> {
+        let mut value = [0u8; 8];
+        read_exact(reader, &mut value)?;
+        let script_pubkey = Script::consensus_decode(reader)?;
+        Ok(TransactionOutput::new(
+            u64::from_le_bytes(value),
+            script_pubkey,
+        ))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        if bytes.len() < 8 {
+            return Err(BitcoinError::InsufficientBytes {
+                needed: 8,
+                got: bytes.len(),
+            });
+        }
+
+        let mut value_bytes = [0u8; 8];
+        value_bytes.copy_from_slice(&bytes[0..8]);
+        let value = u64::from_le_bytes(value_bytes);
+
+        let (script_pubkey, script_bytes) = Script::from_bytes(&bytes[8..])?;
+
+        Ok((
+            TransactionOutput::new(value, script_pubkey),
+            8 + script_bytes,
+        ))
+    }
+
+    // Zero-value OP_RETURN <data> output, limited to the standard 80 bytes
+    pub fn op_return(data: &[u8]) -> Result<TransactionOutput, BitcoinError> {
+        if data.len() > MAX_OP_RETURN_RELAY {
+            return Err(BitcoinError::InvalidFormat);
+        }
+
+        let mut script = Script::new(vec![0x6A]);
+        script.push_data(data);
+
+        Ok(TransactionOutput::new(0, script))
+    }
+
+    // Bitcoin Core's dust rule: an output is dust when it is worth less than
+    // the fee (at `dust_feerate` sat/kvB) to create and later spend it.
+    // Provably unspendable OP_RETURN outputs are never dust.
+    pub fn is_dust(&self, dust_feerate: u64) -> bool {
+        if self.script_pubkey.first() == Some(&0x6A) {
+            return false;
+        }
+        let spend_size = if self.script_pubkey.is_witness_program() {
+            // outpoint + empty scriptSig + sequence + discounted witness
+            32 + 4 + 1 + 4 + 107 / WITNESS_SCALE_FACTOR
+        } else {
+            32 + 4 + 1 + 107 + 4
+        };
+        // In u128 so a huge caller-supplied feerate can't overflow
+        let size = self.to_bytes().len() + spend_size;
+        (self.value.to_sat() as u128) < size as u128 * dust_feerate as u128 / 1000
+    }
+}
+
+// Non-witness bytes weigh this many weight units; witness bytes weigh one
+pub const WITNESS_SCALE_FACTOR: usize = 4;
+
+pub const PSBT_MAGIC: [u8; 5] = *b"psbt\xff";
+
+pub const MAX_STANDARD_TX_WEIGHT: usize = 400_000;
+
+// Consensus limit for a whole block, so also an upper bound for any one tx
+pub const MAX_BLOCK_WEIGHT: usize = 4_000_000;
+
+// BIP125 rule 5: most transactions a replacement may evict, descendants included
+pub const MAX_BIP125_REPLACEMENT_CANDIDATES: usize = 100;
+
+// Smallest non-witness size relayed; a 64-byte transaction could pass for
+// an inner merkle node (CVE-2017-12842)
+pub const MIN_STANDARD_TX_NONWITNESS_SIZE: usize = 65;
+
+pub const SIGHASH_ALL: u32 = 0x01;
+pub const SIGHASH_NONE: u32 = 0x02;
+pub const SIGHASH_SINGLE: u32 = 0x03;
+pub const SIGHASH_ANYONECANPAY: u32 = 0x80;
+
+pub const CONSOLIDATION_MIN_INPUTS: usize = 10;
+pub const CONSOLIDATION_MAX_OUTPUTS: usize = 2;
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct TxSummary {
+    pub txid: String,
+    pub version: u32,
+    pub input_count: usize,
+    pub output_count: usize,
+    pub total_out: u64,
+    pub vsize: usize,
+    pub is_segwit: bool,
+    pub is_coinbase: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct ValueFlow {
+    pub total_in: u64,
+    pub total_out: u64,
+    pub fee: u64,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+pub struct Witness {
+    pub items: Vec<Vec<u8>>,
+}
+
+impl Witness {
+    pub fn new(items: Vec<Vec<u8>>) -> Self {
+        Witness { items }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // BIP341 annex: with two or more items, a last item starting with 0x50.
+    // It is carried as an ordinary item so serialization preserves it as-is.
+    pub fn annex(&self) -> Option<&[u8]> {
+        match self.items.as_slice() {
+            [_, .., last] if last.first() == Some(&0x50) => Some(last),
+            _ => None,
+        }
+    }
+
+    // BIP341 key-path spend: a single Schnorr signature once any annex is
+    // set aside. 64 bytes means SIGHASH_DEFAULT; a 65th byte carries an
+    // explicit sighash type, which may not be 0x00.
+    pub fn taproot_key_spend_sig(&self) -> Option<&[u8]> {
+        let items = match self.annex() {
+            Some(_) => &self.items[..self.items.len() - 1],
+            None => &self.items[..],
+        };
+        match items {
+            [signature] if signature.len() == 64 => Some(signature),
+            [signature] if signature.len() == 65 && signature[64] != 0x00 => Some(signature),
+            _ => None,
+        }
+    }
+
+    // A standard P2WPKH witness is exactly <signature> <compressed pubkey>
+    pub fn p2wpkh_parts(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+        match self.items.as_slice() {
+            [signature, pubkey] if pubkey.len() == 33 && !signature.is_empty() => {
+                Some((signature.clone(), pubkey.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode_to_vec(|bytes| self.consensus_encode(bytes))
+    }
+
+    pub fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        let mut written = CompactSize::new(self.items.len() as u64).consensus_encode(writer)?;
+        for item in &self.items {
+            written += CompactSize::new(item.len() as u64).consensus_encode(writer)?;
+            writer.write_all(item)?;
+            written += item.len();
+        }
+        Ok(written)
+    }
+
+    pub fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, BitcoinError> {
+        let item_count = CompactSize::consensus_decode(reader)?;
+        let mut items = Vec::new();
+        for _ in 0..item_count.value {
+            let length = CompactSize::consensus_decode(reader)?;
+            items.push(read_vec(reader, length.value)?);
+        }
+        Ok(Witness::new(items))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let (item_count, count_bytes) = CompactSize::from_bytes(bytes)?;
+        let mut consumed = count_bytes;
+
+        let mut items = Vec::new();
+        for _ in 0..usize::try_from(item_count)? {
+            let (length, length_bytes) = CompactSize::from_bytes(&bytes[consumed..])?;
+            consumed += length_bytes;
+            let item_length = usize::try_from(length)?;
+
+            items.push(slice_at(bytes, consumed, item_length)?.to_vec());
+            consumed += item_length;
+        }
+
+        Ok((Witness::new(items), consumed))
+    }
+}
+
+// Gathered by `BitcoinTransaction::from_bytes_with_stats`. Byte totals
+// cover the serialized inputs and outputs, excluding their counts.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ParseStats {
+    // Longest scriptSig or scriptPubKey; witness items are not scripts here
+    pub max_script_len: usize,
+    pub total_input_bytes: usize,
+    pub total_output_bytes: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct BitcoinTransaction {
+    pub version: u32,
+    pub inputs: Vec<TransactionInput>,
+    pub outputs: Vec<TransactionOutput>,
+    // One witness per input, or empty for a legacy transaction
+    #[serde(default)]
+    pub witnesses: Vec<Witness>,
+    pub lock_time: LockTime,
+}
+
+// Locktime values below this are block heights, the rest Unix timestamps
+pub const LOCK_TIME_THRESHOLD: u32 = 500_000_000;
+
+// A transaction's nLockTime, split by how consensus reads it
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
+#[serde(from = "u32", into = "u32")]
+pub enum LockTime {
+    Blocks(u32),
+    Time(u32),
+}
+
+impl LockTime {
+    pub fn from_u32(lock_time: u32) -> Self {
+        if lock_time < LOCK_TIME_THRESHOLD {
+            LockTime::Blocks(lock_time)
+        } else {
+            LockTime::Time(lock_time)
+        }
+    }
+
+    pub fn to_u32(self) -> u32 {
+        match self {
+            LockTime::Blocks(height) => height,
+            LockTime::Time(timestamp) => timestamp,
+        }
+    }
+
+    pub fn is_block_height(self) -> bool {
+        matches!(self, LockTime::Blocks(_))
+    }
+
+    pub fn is_timestamp(self) -> bool {
+        matches!(self, LockTime::Time(_))
+    }
+}
+
+impl From<u32> for LockTime {
+    fn from(lock_time: u32) -> Self {
+        LockTime::from_u32(lock_time)
+    }
+}
+
+impl From<LockTime> for u32 {
+    fn from(lock_time: LockTime) -> Self {
+        lock_time.to_u32()
+    }
+}
+
+impl BitcoinTransaction {
+    pub fn new(
+        version: u32,
+        inputs: Vec<TransactionInput>,
+        outputs: Vec<TransactionOutput>,
+        lock_time: u32,
+    ) -> Self {
+        BitcoinTransaction {
+            version,
+            inputs,
+            outputs,
+            witnesses: Vec::new(),
+            lock_time: LockTime::from_u32(lock_time),
+        }
+    }
+
+    pub fn with_witnesses(mut self, witnesses: Vec<Witness>) -> Self {
+        self.witnesses = witnesses;
+        self
+    }
+
+    pub fn has_witness(&self) -> bool {
+        self.witnesses.iter().any(|witness| !witness.is_empty())
+    }
+
+    // Full serialization, using the BIP144 segwit layout when any input has a
+    // witness
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.serialize(self.has_witness())
+    }
+
+    // Serialization without marker, flag or witnesses, as hashed for the txid
+    pub fn to_bytes_legacy(&self) -> Vec<u8> {
+        self.serialize(false)
+    }
+
+    // The exact preimage hashed by `txid()`/`compute_txid`: legacy layout,
+    // inputs and outputs in their current order, nothing sorted or stripped
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        self.to_bytes_legacy()
+    }
+
+    fn serialize(&self, include_witness: bool) -> Vec<u8> {
+        encode_to_vec(|bytes| self.encode_with(bytes, include_witness))
+    }
+
+    fn serialized_size(&self, include_witness: bool) -> usize {
+        encoded_size(|counter| self.encode_with(counter, include_witness))
+    }
+
+    // Streams the same bytes as `to_bytes` into `writer`, returning how many
+    // were written
+    pub fn consensus_encode<W: Write>(&self, writer: &mut W) -> io::Result<usize> {
+        self.encode_with(writer, self.has_witness())
+    }
+
+    fn encode_with<W: Write>(&self, writer: &mut W, include_witness: bool) -> io::Result<usize> {
+        // Version (4 bytes LE)
+        writer.write_all(&self.version.to_le_bytes())?;
+        let mut written = 4;
+
+        // Segwit marker and flag
+        if include_witness {
+            writer.write_all(&[0x00, 0x01])?;
+            written += 2;
+        }
+
+        // Input count (CompactSize) and each input
+        written += CompactSize::new(self.inputs.len() as u64).consensus_encode(writer)?;
+        for input in &self.inputs {
+            written += input.consensus_encode(writer)?;
+        }
+
+        // Output count (CompactSize) and each output
+        written += CompactSize::new(self.outputs.len() as u64).consensus_encode(writer)?;
+        for output in &self.outputs {
+            written += output.consensus_encode(writer)?;
+        }
+
+        // One witness per input
+        if include_witness {
+            for i in 0..self.inputs.len() {
+                match self.witnesses.get(i) {
+                    Some(witness) => written += witness.consensus_encode(writer)?,
+                    None => {
+                        writer.write_all(&[0x00])?;
+                        written += 1;
+                    }
+                }
+            }
+        }
+
+        // Lock time (4 bytes LE)
+        writer.write_all(&self.lock_time.to_u32().to_le_bytes())?;
+
+        Ok(written + 4)
+    }
+
+    // Reads exactly one transaction from `reader`, leaving anything after it
+    // unread. Elements are only allocated as their bytes arrive, so a bogus
+    // count fails at end of stream rather than up front.
+    pub fn consensus_decode<R: Read>(reader: &mut R) -> Result<Self, BitcoinError> {
+        let mut version = [0u8; 4];
+        read_exact(reader, &mut version)?;
+
+        // A leading 0x00 is either the segwit marker or, with a second 0x00,
+        // a legacy transaction with no inputs and no outputs
+        let mut prefix = [0u8; 1];
+        read_exact(reader, &mut prefix)?;
+        let mut segwit = false;
+        let mut empty_legacy = false;
+        let input_count = if prefix[0] == 0x00 {
+            let mut flag = [0u8; 1];
+            read_exact(reader, &mut flag)?;
+            match flag[0] {
+                0x00 => {
+                    empty_legacy = true;
+                    0
+                }
+                0x01 => {
+                    segwit = true;
+                    CompactSize::consensus_decode(reader)?.value
+                }
+                _ => return Err(BitcoinError::InvalidFormat),
+            }
+        } else {
+            CompactSize::decode_after_prefix(prefix[0], reader)?.value
+        };
+
+        let mut inputs = Vec::new();
+        for _ in 0..input_count {
+            inputs.push(TransactionInput::consensus_decode(reader)?);
+        }
+
+        let output_count = if empty_legacy {
+            0
+        } else {
+            CompactSize::consensus_decode(reader)?.value
+        };
+        let mut outputs = Vec::new();
+        for _ in 0..output_count {
+            outputs.push(TransactionOutput::consensus_decode(reader)?);
+        }
+
+        let mut witnesses = Vec::new();
+        if segwit {
+            for _ in 0..inputs.len() {
+                witnesses.push(Witness::consensus_decode(reader)?);
+            }
+            // Superfluous witness record: re-encoding would drop the marker
+            if witnesses.iter().all(Witness::is_empty) {
+                return Err(BitcoinError::InvalidFormat);
+            }
+        }
+
+        let mut lock_time = [0u8; 4];
+        read_exact(reader, &mut lock_time)?;
+
+        Ok(BitcoinTransaction::new(
+            u32::from_le_bytes(version),
+            inputs,
+            outputs,
+            u32::from_le_bytes(lock_time),
+        )
+        .with_witnesses(witnesses))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        Self::from_bytes_with_limit(bytes, usize::MAX)
+    }
+
+    // Strict entry point for a buffer that must hold exactly one transaction
+    // and nothing else. Use `from_bytes` when more data may follow.
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        let (tx, consumed) = BitcoinTransaction::from_bytes(bytes)?;
+        if consumed != bytes.len() {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        Ok(tx)
+    }
+
+    // Same as `from_bytes`, but rejects transactions declaring more than
+    // `max_inputs` inputs before any of them are parsed
+    pub fn from_bytes_with_limit(
+        bytes: &[u8],
+        max_inputs: usize,
+    ) -> Result<(Self, usize), BitcoinError> {
+        Self::parse(bytes, max_inputs, true, &mut ParseStats::default())
+    }
+
+    // Parses a buffer holding exactly one transaction, gathering size
+    // statistics in the same pass
+    pub fn from_bytes_with_stats(bytes: &[u8]) -> Result<(Self, ParseStats), BitcoinError> {
+        let mut stats = ParseStats::default();
+        let (tx, consumed) = Self::parse(bytes, usize::MAX, true, &mut stats)?;
+        if consumed != bytes.len() {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        Ok((tx, stats))
+    }
+
+    // Like `deserialize`, but only accepts the legacy layout, so a leading
+    // 0x00 is always an input count of zero rather than the segwit marker
+    fn deserialize_legacy(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        let (tx, consumed) = Self::parse(bytes, usize::MAX, false, &mut ParseStats::default())?;
+        if consumed != bytes.len() {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        Ok(tx)
+    }
+
+    fn parse(
+        bytes: &[u8],
+        max_inputs: usize,
+        allow_witness: bool,
+        stats: &mut ParseStats,
+    ) -> Result<(Self, usize), BitcoinError> {
+        let mut consumed = 0;
+
+        // Parse version
+        if bytes.len() < 4 {
+            return Err(BitcoinError::InsufficientBytes {
+                needed: 4,
+                got: bytes.len(),
+            });
         }
         let version = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
         consumed += 4;
 
-        // Parse input count
-        let (input_count, count_bytes) = CompactSize::from_bytes(&bytes[consumed..])?;
-        consumed += count_bytes;
+        // Detect segwit marker and flag
+        let segwit = allow_witness && bytes.len() >= 6 && bytes[4] == 0x00 && bytes[5] != 0x00;
+        if segwit {
+            if bytes[5] != 0x01 {
+                return Err(BitcoinError::InvalidFormat);
+            }
+            consumed += 2;
+        }
+
+        // Parse input count
+        let (input_count, count_bytes) = CompactSize::from_bytes(&bytes[consumed..])?;
+        consumed += count_bytes;
+
+        let input_count = usize::try_from(input_count)?;
+        if input_count > max_inputs {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        // Fail fast on counts the remaining buffer cannot possibly hold
+        check_count_fits(input_count, MIN_INPUT_SIZE, bytes.len() - consumed)?;
+
+        // Parse inputs
+        let mut inputs = Vec::new();
+        for _ in 0..input_count {
+            let (input, input_bytes) = TransactionInput::from_bytes(&bytes[consumed..])?;
+            stats.max_script_len = stats.max_script_len.max(input.script_sig.len());
+            stats.total_input_bytes += input_bytes;
+            inputs.push(input);
+            consumed += input_bytes;
+        }
+
+        // Parse output count
+        let (output_count, count_bytes) = CompactSize::from_bytes(&bytes[consumed..])?;
+        consumed += count_bytes;
+        let output_count = usize::try_from(output_count)?;
+        check_count_fits(output_count, MIN_OUTPUT_SIZE, bytes.len() - consumed)?;
+
+        // Parse outputs
+        let mut outputs = Vec::new();
+        for _ in 0..output_count {
+            let (output, output_bytes) = TransactionOutput::from_bytes(&bytes[consumed..])?;
+            stats.max_script_len = stats.max_script_len.max(output.script_pubkey.len());
+            stats.total_output_bytes += output_bytes;
+            outputs.push(output);
+            consumed += output_bytes;
+        }
+
+        // Parse witnesses
+        let mut witnesses = Vec::new();
+        if segwit {
+            for _ in 0..inputs.len() {
+                let (witness, witness_bytes) = Witness::from_bytes(&bytes[consumed..])?;
+                witnesses.push(witness);
+                consumed += witness_bytes;
+            }
+            // A marker with only empty witnesses is a superfluous witness
+            // record, which would not survive a round-trip through `to_bytes`
+            if witnesses.iter().all(Witness::is_empty) {
+                return Err(BitcoinError::InvalidFormat);
+            }
+        }
+
+        // Parse lock time
+        if bytes.len() < consumed + 4 {
+            return Err(BitcoinError::InsufficientBytes {
+                needed: consumed + 4,
+                got: bytes.len(),
+            });
+        }
+        let lock_time = u32::from_le_bytes([
+            bytes[consumed],
+            bytes[consumed + 1],
+            bytes[consumed + 2],
+            bytes[consumed + 3],
+        ]);
+        consumed += 4;
+
+        Ok((
+            BitcoinTransaction::new(version, inputs, outputs, lock_time).with_witnesses(witnesses),
+            consumed,
+        ))
+    }
+
+    // Compact JSON of the serde representation with object keys sorted at
+    // every level, independent of serde_json's `preserve_order` feature, for
+    // snapshot tests and content addressing
+    pub fn to_stable_json(&self) -> String {
+        let value = serde_json::to_value(self).expect("transactions always serialize to JSON");
+        let mut json = String::new();
+        write_stable_json(&value, &mut json);
+        json
+    }
+
+    // Reproducible pseudo-random transaction for fixtures and benchmarks:
+    // 1-3 signed-looking P2WPKH inputs and 1-3 P2WPKH outputs, all derived
+    // from `seed`. Not a valid spend, but it parses and round-trips.
+    pub fn deterministic_sample(seed: u64) -> BitcoinTransaction {
+        let mut state = seed;
+
+        let input_count = 1 + splitmix64(&mut state) % 3;
+        let mut inputs = Vec::new();
+        let mut witnesses = Vec::new();
+        for _ in 0..input_count {
+            let mut txid = [0u8; 32];
+            for chunk in txid.chunks_mut(8) {
+                chunk.copy_from_slice(&splitmix64(&mut state).to_le_bytes());
+            }
+            let vout = (splitmix64(&mut state) % 4) as u32;
+            inputs.push(TransactionInput::new(
+                OutPoint::new(txid, vout),
+                Script::new(Vec::new()),
+                SEQUENCE_RBF,
+            ));
+
+            let signature_len = 71 + (splitmix64(&mut state) % 2) as usize;
+            let filler = splitmix64(&mut state) as u8;
+            witnesses.push(Witness::new(vec![
+                vec![filler; signature_len],
+                vec![0x02; 33],
+            ]));
+        }
+
+        let output_count = 1 + splitmix64(&mut state) % 3;
+        let outputs = (0..output_count)
+            .map(|_| {
+                let value = 546 + splitmix64(&mut state) % 100_000_000;
+                let mut script = vec![0x00, 0x14];
+                script.extend_from_slice(&splitmix64(&mut state).to_le_bytes());
+                script.extend_from_slice(&[0u8; 12]);
+                TransactionOutput::new(value, Script::new(script))
+            })
+            .collect();
+
+        BitcoinTransaction::new(2, inputs, outputs, 0).with_witnesses(witnesses)
+    }
+
+    // How many more bytes a partial serialization needs before the parse can
+    // advance: 0 once it is complete. This is the shortfall at the first
+    // point that ran out, so a badly truncated buffer may need more after it.
+    pub fn bytes_needed(partial: &[u8]) -> Result<usize, BitcoinError> {
+        match BitcoinTransaction::from_bytes(partial) {
+            Ok(_) => Ok(0),
+            Err(BitcoinError::InsufficientBytes { needed, got }) => Ok(needed - got),
+            Err(err) => Err(err),
+        }
+    }
+
+    // Serializes without touching the heap for transactions up to 256 bytes,
+    // which covers the common single-input single-output case
+    #[cfg(feature = "smallvec")]
+    pub fn to_smallvec(&self) -> smallvec::SmallVec<[u8; 256]> {
+        let mut bytes = smallvec::SmallVec::new();
+        self.consensus_encode(&mut bytes)
+            .expect("writing to a SmallVec cannot fail");
+        bytes
+    }
+
+    // Length-prefixed framing for append-only logs: a 4-byte LE length
+    // followed by the standard serialization
+    pub fn to_compact_storage(&self) -> Vec<u8> {
+        let tx_bytes = self.to_bytes();
+        let mut bytes = Vec::with_capacity(4 + tx_bytes.len());
+        bytes.extend_from_slice(&(tx_bytes.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&tx_bytes);
+        bytes
+    }
+
+    pub fn from_compact_storage_stream<R: Read>(reader: &mut R) -> Result<Self, BitcoinError> {
+        let mut length = [0u8; 4];
+        read_exact(reader, &mut length)?;
+
+        let tx_bytes = read_vec(reader, u32::from_le_bytes(length) as u64)?;
+
+        BitcoinTransaction::deserialize(&tx_bytes)
+    }
+
+    // Body of Bitcoin Core's `/rest/tx/<txid>.bin`: the bare serialization,
+    // witnesses included, with nothing before or after it
+    pub fn from_rest_bytes(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        BitcoinTransaction::deserialize(bytes)
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.to_bytes())
+    }
+
+    // Parses hex such as `getrawtransaction` output, as strictly as
+    // `deserialize`
+    pub fn from_hex(s: &str) -> Result<Self, BitcoinError> {
+        let bytes = hex::decode(s).map_err(|_| BitcoinError::InvalidFormat)?;
+        BitcoinTransaction::deserialize(&bytes)
+    }
+
+    pub fn to_base64(&self) -> String {
+        BASE64.encode(self.to_bytes())
+    }
+
+    pub fn from_base64(s: &str) -> Result<Self, BitcoinError> {
+        let bytes = BASE64.decode(s).map_err(|_| BitcoinError::InvalidFormat)?;
+        BitcoinTransaction::deserialize(&bytes)
+    }
+
+    // Minimal BIP174 PSBT: the unsigned transaction (scriptSigs and witnesses
+    // stripped) as the only global, followed by empty input and output maps
+    pub fn to_psbt(&self) -> Vec<u8> {
+        let mut unsigned = self.clone();
+        for input in &mut unsigned.inputs {
+            input.script_sig = Script::new(Vec::new());
+        }
+        unsigned.witnesses.clear();
+        let unsigned_bytes = unsigned.to_bytes_legacy();
+
+        let mut bytes = PSBT_MAGIC.to_vec();
+
+        // PSBT_GLOBAL_UNSIGNED_TX: key length 1, key type 0x00
+        bytes.extend_from_slice(&[0x01, 0x00]);
+        bytes.extend_from_slice(&CompactSize::new(unsigned_bytes.len() as u64).to_bytes());
+        bytes.extend_from_slice(&unsigned_bytes);
+        bytes.push(0x00);
+
+        // Empty per-input and per-output maps
+        bytes.extend(core::iter::repeat_n(
+            0x00,
+            self.inputs.len() + self.outputs.len(),
+        ));
+
+        bytes
+    }
+
+    // Reads the global unsigned transaction from a PSBT, ignoring every other
+    // field
+    pub fn from_psbt(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        if bytes.len() < PSBT_MAGIC.len() || bytes[..PSBT_MAGIC.len()] != PSBT_MAGIC {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        let mut consumed = PSBT_MAGIC.len();
+
+        let mut unsigned_tx = None;
+        loop {
+            let (key_length, key_length_bytes) = CompactSize::from_bytes(&bytes[consumed..])?;
+            consumed += key_length_bytes;
+
+            // A zero-length key is the separator ending the global map
+            if key_length.value == 0 {
+                break;
+            }
+            let key_length = usize::try_from(key_length)?;
+            let key = slice_at(bytes, consumed, key_length)?;
+            consumed += key_length;
+
+            let (value_length, value_length_bytes) = CompactSize::from_bytes(&bytes[consumed..])?;
+            consumed += value_length_bytes;
+            let value_length = usize::try_from(value_length)?;
+            let value = slice_at(bytes, consumed, value_length)?;
+            consumed += value_length;
+
+            // BIP174 stores the unsigned tx in the non-witness layout
+            if key == [0x00] {
+                unsigned_tx = Some(BitcoinTransaction::deserialize_legacy(value)?);
+            }
+        }
+
+        unsigned_tx.ok_or(BitcoinError::InvalidFormat)
+    }
+
+    // Double SHA-256 of the legacy serialization; witness data never affects
+    // the txid, see `wtxid` for the hash that commits to it
+    pub fn txid(&self) -> Txid {
+        compute_txid(&self.canonical_bytes())
+    }
+
+    // Double SHA-256 of the full serialization, witnesses included (BIP141).
+    // Equal to the txid when no input has a witness. Block merkle roots use
+    // txids; only the witness commitment in the coinbase uses wtxids.
+    pub fn wtxid(&self) -> Txid {
+        Txid(hash256(&self.to_bytes()))
+    }
+
+    // First 8 bytes of the txid as displayed, the usual short form in UIs
+    pub fn fingerprint(&self) -> [u8; 8] {
+        let txid = self.txid().0;
+        let mut fingerprint = [0u8; 8];
+        for (dst, src) in fingerprint.iter_mut().zip(txid.iter().rev()) {
+            *dst = *src;
+        }
+        fingerprint
+    }
+
+    pub fn to_getdata_inv(&self, use_wtxid: bool) -> InvVector {
+        if use_wtxid {
+            InvVector::new(MSG_WTX, self.wtxid().0)
+        } else {
+            InvVector::new(MSG_TX, self.txid().0)
+        }
+    }
+
+    // BIP69: inputs by (txid in display order, vout), outputs by (value,
+    // scriptPubKey). Witnesses are kept with their inputs.
+    pub fn sort_bip69(&mut self) {
+        if self.has_witness() {
+            self.witnesses.resize(self.inputs.len(), Witness::default());
+            let mut paired: Vec<(TransactionInput, Witness)> = self
+                .inputs
+                .drain(..)
+                .zip(self.witnesses.drain(..))
+                .collect();
+            paired.sort_by(|a, b| bip69_input_order(&a.0, &b.0));
+            let (inputs, witnesses) = paired.into_iter().unzip();
+            self.inputs = inputs;
+            self.witnesses = witnesses;
+        } else {
+            self.inputs.sort_by(bip69_input_order);
+        }
+
+        self.outputs.sort_by(|a, b| {
+            a.value
+                .cmp(&b.value)
+                .then_with(|| a.script_pubkey.bytes.cmp(&b.script_pubkey.bytes))
+        });
+    }
+
+    pub fn sorted_bip69(&self) -> BitcoinTransaction {
+        let mut sorted = self.clone();
+        sorted.sort_bip69();
+        sorted
+    }
+
+    // A coinbase has exactly one input, spending the null outpoint
+    pub fn is_coinbase(&self) -> bool {
+        match self.inputs.as_slice() {
+            [input] => input.previous_output.is_null(),
+            _ => false,
+        }
+    }
+
+    pub fn summary(&self) -> TxSummary {
+        TxSummary {
+            txid: self.txid().to_string(),
+            version: self.version,
+            input_count: self.inputs.len(),
+            output_count: self.outputs.len(),
+            total_out: self.outputs.iter().fold(0u64, |total, output| {
+                total.saturating_add(output.value.to_sat())
+            }),
+            vsize: self.vsize(),
+            is_segwit: self.has_witness(),
+            is_coinbase: self.is_coinbase(),
+        }
+    }
+
+    // BIP141 weight: non-witness bytes count four times, witness bytes once
+    // Sizes come from counting encoder output, so nothing is serialized
+    pub fn weight(&self) -> usize {
+        let base_size = self.serialized_size(false);
+        if !self.has_witness() {
+            return base_size * WITNESS_SCALE_FACTOR;
+        }
+        base_size * (WITNESS_SCALE_FACTOR - 1) + self.serialized_size(true)
+    }
+
+    // The BIP144 marker and flag are witness data, so they weigh 1 WU each
+    // and only appear when some input has a witness
+    pub fn marker_flag_weight(&self) -> usize {
+        if self.has_witness() { 2 } else { 0 }
+    }
+
+    pub fn vsize(&self) -> usize {
+        weight_to_vsize(self.weight() as u64) as usize
+    }
+
+    // Inputs with a witness must have an empty scriptSig (native segwit) or a
+    // single push of a witness program (P2SH-wrapped segwit)
+    pub fn validate_segwit_scriptsigs(&self) -> Result<(), BitcoinError> {
+        for (input, witness) in self.inputs.iter().zip(&self.witnesses) {
+            if witness.is_empty() || input.script_sig.is_empty() {
+                continue;
+            }
+            let script_sig = &input.script_sig.bytes;
+            let redeem_script = Script::new(script_sig[1..].to_vec());
+            if script_sig[0] as usize != redeem_script.len() || !redeem_script.is_witness_program()
+            {
+                return Err(BitcoinError::InvalidFormat);
+            }
+        }
+        Ok(())
+    }
+
+    // Segwit-aware sigop cost: legacy and P2SH sigops weigh
+    // WITNESS_SCALE_FACTOR, witness sigops weigh 1. `prevout_scripts` holds
+    // the scriptPubKey spent by each input.
+    pub fn sigop_cost(&self, prevout_scripts: &[Script]) -> usize {
+        let mut legacy = 0;
+        for input in &self.inputs {
+            legacy += input.script_sig.sigop_count(false);
+        }
+        for output in &self.outputs {
+            legacy += output.script_pubkey.sigop_count(false);
+        }
+
+        let mut witness = 0;
+        for (i, (input, prevout)) in self.inputs.iter().zip(prevout_scripts).enumerate() {
+            let mut program = prevout.clone();
+            if prevout.classify() == ScriptType::P2SH
+                && let Some(redeem_script) = input.script_sig.last_push()
+            {
+                program = Script::new(redeem_script);
+                legacy += program.sigop_count(true);
+            }
+
+            let empty = Witness::default();
+            let items = &self.witnesses.get(i).unwrap_or(&empty).items;
+            witness += match program.classify() {
+                ScriptType::P2WPKH => 1,
+                ScriptType::P2WSH => items
+                    .last()
+                    .map_or(0, |script| Script::new(script.clone()).sigop_count(true)),
+                _ => 0,
+            };
+        }
+
+        legacy * WITNESS_SCALE_FACTOR + witness
+    }
+
+    // Consensus "bad-txns-vout-toolarge"/"txouttotal-toolarge": every output
+    // and the running total must stay within MAX_MONEY (so never overflow)
+    pub fn validate_output_values(&self) -> Result<(), BitcoinError> {
+        let mut total: u64 = 0;
+        for output in &self.outputs {
+            if output.value.to_sat() > MAX_MONEY {
+                return Err(BitcoinError::InvalidFormat);
+            }
+            total = total
+                .checked_add(output.value.to_sat())
+                .filter(|total| *total <= MAX_MONEY)
+                .ok_or(BitcoinError::InvalidFormat)?;
+        }
+        Ok(())
+    }
+
+    pub fn validate_output_scripts(&self) -> Result<(), BitcoinError> {
+        for (index, output) in self.outputs.iter().enumerate() {
+            output
+                .script_pubkey
+                .validate_pushes()
+                .map_err(|_| BitcoinError::InvalidOutputScript { index })?;
+        }
+        Ok(())
+    }
+
+    // Fee saved by the witness discount: the gap between paying for every
+    // serialized byte at full weight and paying for the actual vsize
+    pub fn segwit_fee_savings(&self, feerate_sat_per_vb: u64) -> u64 {
+        let undiscounted = self.to_bytes().len();
+        ((undiscounted - self.vsize()) as u64).saturating_mul(feerate_sat_per_vb)
+    }
+
+    pub fn exceeds_standard_weight(&self) -> bool {
+        self.weight() > MAX_STANDARD_TX_WEIGHT
+    }
+
+    pub fn meets_min_nonwitness_size(&self) -> bool {
+        self.serialized_size(false) >= MIN_STANDARD_TX_NONWITNESS_SIZE
+    }
+
+    pub fn fits_in_block(&self) -> bool {
+        self.weight() <= MAX_BLOCK_WEIGHT
+    }
+
+    // The vsize this transaction would have with `witnesses` attached (one
+    // per input, replacing any current ones), without modifying it
+    pub fn vsize_with_witness(&self, witnesses: &[Witness]) -> usize {
+        let base_size = self.serialized_size(false);
+        let mut weight = base_size * WITNESS_SCALE_FACTOR;
+        if witnesses.iter().any(|witness| !witness.is_empty()) {
+            // Marker, flag, and one (possibly empty) stack per input
+            weight += 2;
+            for i in 0..self.inputs.len() {
+                weight += witnesses.get(i).map_or(1, |witness| {
+                    encoded_size(|counter| witness.consensus_encode(counter))
+                });
+            }
+        }
+        weight_to_vsize(weight as u64) as usize
+    }
+
+    // Fraction of the serialized size taken by the marker, flag and witnesses
+    pub fn witness_byte_ratio(&self) -> f64 {
+        if !self.has_witness() {
+            return 0.0;
+        }
+        let total = self.to_bytes().len();
+        let base = self.to_bytes_legacy().len();
+        (total - base) as f64 / total as f64
+    }
+
+    pub fn is_consolidation(&self) -> bool {
+        self.is_consolidation_with(CONSOLIDATION_MIN_INPUTS, CONSOLIDATION_MAX_OUTPUTS)
+    }
+
+    pub fn is_consolidation_with(&self, min_inputs: usize, max_outputs: usize) -> bool {
+        self.inputs.len() >= min_inputs && self.outputs.len() <= max_outputs
+    }
+
+    // One input, one output: the whole coin moved to a single destination
+    pub fn is_sweep(&self) -> bool {
+        self.inputs.len() == 1 && self.outputs.len() == 1
+    }
+
+    // Distinct parents in input order, skipping the coinbase null outpoint
+    pub fn parent_txids(&self) -> Vec<Txid> {
+        let mut parents: Vec<Txid> = Vec::new();
+        for input in &self.inputs {
+            let txid = &input.previous_output.txid;
+            if !input.previous_output.is_null() && !parents.contains(txid) {
+                parents.push(txid.clone());
+            }
+        }
+        parents
+    }
+
+    // Vouts needed from each parent, in input order, so each parent is
+    // fetched once. The coinbase null prevout has no parent and is skipped.
+    #[cfg(feature = "std")]
+    pub fn prevout_fetch_plan(&self) -> HashMap<Txid, Vec<u32>> {
+        let mut plan: HashMap<Txid, Vec<u32>> = HashMap::new();
+        for input in &self.inputs {
+            let outpoint = &input.previous_output;
+            if !outpoint.is_null() {
+                plan.entry(outpoint.txid.clone())
+                    .or_default()
+                    .push(outpoint.vout);
+            }
+        }
+        plan
+    }
+
+    // hash256(outpoint || serialized output) for output `index`, binding the
+    // output's contents to its position in this transaction
+    pub fn output_commitment(&self, index: usize) -> Result<[u8; 32], BitcoinError> {
+        let output = self.outputs.get(index).ok_or(BitcoinError::InvalidFormat)?;
+        let mut bytes = OutPoint::new(self.txid().0, index as u32).to_bytes();
+        bytes.extend_from_slice(&output.to_bytes());
+        Ok(hash256(&bytes))
+    }
+
+    // The outpoints this transaction creates, each with its output
+    pub fn created_outputs(&self) -> Vec<(OutPoint, &TxOut)> {
+        let txid = self.txid();
+        self.outputs
+            .iter()
+            .enumerate()
+            .map(|(vout, output)| (OutPoint::new(txid.0, vout as u32), output))
+            .collect()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn owned_outputs(&self, my_scripts: &HashSet<Vec<u8>>) -> Vec<(usize, u64)> {
+        self.outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| my_scripts.contains(&output.script_pubkey.bytes))
+            .map(|(index, output)| (index, output.value.to_sat()))
+            .collect()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn spends_from(&self, my_outpoints: &HashSet<OutPoint>) -> Vec<usize> {
+        self.inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, input)| my_outpoints.contains(&input.previous_output))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    // Shannon entropy of the serialization in bits per byte (0.0 to 8.0)
+    #[cfg(feature = "std")]
+    pub fn serialized_entropy(&self) -> f64 {
+        let bytes = self.to_bytes();
+        let mut counts = [0usize; 256];
+        for byte in &bytes {
+            counts[*byte as usize] += 1;
+        }
+
+        let total = bytes.len() as f64;
+        counts
+            .iter()
+            .filter(|count| **count > 0)
+            .map(|count| {
+                let p = *count as f64 / total;
+                -p * p.log2()
+            })
+            .sum()
+    }
+
+    // Pre-segwit signature hash for input `input_index`: the transaction with
+    // every scriptSig blanked except this input's, which becomes
+    // `script_code`, followed by the 4-byte sighash type. NONE, SINGLE and
+    // ANYONECANPAY are applied as consensus does, including SINGLE's "hash
+    // of one" for an input without a matching output. OP_CODESEPARATOR is
+    // not stripped from `script_code`.
+    pub fn legacy_sighash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        sighash_type: u32,
+    ) -> Result<[u8; 32], BitcoinError> {
+        if input_index >= self.inputs.len() {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        let base_type = sighash_type & 0x1F;
+        if base_type == SIGHASH_SINGLE && input_index >= self.outputs.len() {
+            let mut one = [0u8; 32];
+            one[0] = 0x01;
+            return Ok(one);
+        }
+
+        let mut tx = self.clone();
+        tx.witnesses.clear();
+        for (i, input) in tx.inputs.iter_mut().enumerate() {
+            input.script_sig = if i == input_index {
+                script_code.clone()
+            } else {
+                Script::new(Vec::new())
+            };
+        }
+
+        if base_type == SIGHASH_NONE || base_type == SIGHASH_SINGLE {
+            if base_type == SIGHASH_NONE {
+                tx.outputs.clear();
+            } else {
+                // Keep outputs up to ours, blanking the earlier ones to value
+                // -1 with an empty script
+                tx.outputs.truncate(input_index + 1);
+                for output in &mut tx.outputs[..input_index] {
+                    *output = TransactionOutput::new(u64::MAX, Script::new(Vec::new()));
+                }
+            }
+            // Other inputs may be updated freely
+            for (i, input) in tx.inputs.iter_mut().enumerate() {
+                if i != input_index {
+                    input.sequence = Sequence::from_u32(0);
+                }
+            }
+        }
+
+        if sighash_type & SIGHASH_ANYONECANPAY != 0 {
+            tx.inputs = vec![tx.inputs.swap_remove(input_index)];
+        }
+
+        let mut preimage = tx.to_bytes_legacy();
+        preimage.extend_from_slice(&sighash_type.to_le_bytes());
+        Ok(hash256(&preimage))
+    }
+
+    // The BIP143 signature message for segwit v0 input `input_index`, as hex
+    // and before hashing, so each field can be read off in order: version,
+    // hashPrevouts, hashSequence, outpoint, scriptCode, amount, nSequence,
+    // hashOutputs, locktime, sighash type
+    pub fn sighash_preimage_hex(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        amount: u64,
+        sighash_type: u32,
+    ) -> Result<String, BitcoinError> {
+        let preimage = SighashCache::new(self).bip143_preimage(
+            input_index,
+            script_code,
+            amount,
+            sighash_type,
+        )?;
+        Ok(hex::encode(preimage))
+    }
+
+    // BIP143 signature hash for segwit v0 input `input_index`, which commits
+    // to the spent `value`. Use `SighashCache` directly when signing several
+    // inputs so the shared midstates are hashed once.
+    pub fn segwit_sighash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        value: u64,
+        sighash_type: u32,
+    ) -> Result<[u8; 32], BitcoinError> {
+        SighashCache::new(self).segwit_sighash(input_index, script_code, value, sighash_type)
+    }
+
+    // Sum of output values in satoshis, or None on overflow
+    pub fn total_output_value(&self) -> Option<u64> {
+        self.outputs
+            .iter()
+            .try_fold(Amount::ZERO, |total, output| {
+                total.checked_add(output.value)
+            })
+            .map(Amount::to_sat)
+    }
+
+    // Fee given the amount spent by each input, in input order. None when the
+    // lengths differ, a sum overflows, or the outputs exceed the inputs.
+    pub fn fee(&self, prevout_values: &[u64]) -> Option<u64> {
+        self.value_flow(prevout_values).ok().map(|flow| flow.fee)
+    }
+
+    // Fee divided by weight rather than vsize: a quarter of the sat/vB rate,
+    // give or take vsize rounding
+    pub fn feerate_sat_per_wu(&self, input_values: &[u64]) -> Result<f64, BitcoinError> {
+        let fee = self.fee(input_values).ok_or(BitcoinError::InvalidFormat)?;
+        Ok(fee as f64 / self.weight() as f64)
+    }
+
+    // `input_values` are the amounts spent by each input, in input order
+    pub fn value_flow(&self, input_values: &[u64]) -> Result<ValueFlow, BitcoinError> {
+        if input_values.len() != self.inputs.len() {
+            return Err(BitcoinError::InvalidFormat);
+        }
+
+        let total_in = input_values
+            .iter()
+            .try_fold(0u64, |total, value| total.checked_add(*value))
+            .ok_or(BitcoinError::InvalidFormat)?;
+        let total_out = self
+            .total_output_value()
+            .ok_or(BitcoinError::InvalidFormat)?;
+        let fee = total_in
+            .checked_sub(total_out)
+            .ok_or(BitcoinError::InvalidFormat)?;
+
+        Ok(ValueFlow {
+            total_in,
+            total_out,
+            fee,
+        })
+    }
+
+    // Legacy (pre-0.15) priority: sum of input value times confirmations,
+    // divided by size. Each entry is (age in blocks, value in sats).
+    pub fn coin_age_priority(&self, input_ages_and_values: &[(u32, u64)]) -> f64 {
+        let coin_age: f64 = input_ages_and_values
+            .iter()
+            .map(|(age, value)| *age as f64 * *value as f64)
+            .sum();
+        coin_age / self.vsize() as f64
+    }
+
+    // A Graphviz record node with one port per input (`in0`, `in1`, ...) and
+    // per output (`out0`, ...), for stitching into a transaction graph
+    pub fn to_dot(&self) -> String {
+        let txid = self.txid().to_string();
+
+        let inputs: Vec<String> = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                format!(
+                    "<in{}> {}:{}",
+                    i, input.previous_output.txid, input.previous_output.vout
+                )
+            })
+            .collect();
+        let outputs: Vec<String> = self
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(i, output)| format!("<out{}> {}: {} sat", i, i, output.value.to_sat()))
+            .collect();
+
+        format!(
+            "\"tx_{}\" [shape=record, label=\"{{{{{}}}|{}|{{{}}}}}\"];\n",
+            txid,
+            inputs.join("|"),
+            txid,
+            outputs.join("|")
+        )
+    }
+
+    // Aligned text tables of the inputs and outputs, for CLI display
+    pub fn to_table(&self) -> String {
+        let input_rows: Vec<Vec<String>> = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                vec![
+                    i.to_string(),
+                    format!(
+                        "{}:{}",
+                        input.previous_output.txid, input.previous_output.vout
+                    ),
+                    format!("0x{:08x}", input.sequence.to_u32()),
+                ]
+            })
+            .collect();
+        let output_rows: Vec<Vec<String>> = self
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(i, output)| {
+                vec![
+                    i.to_string(),
+                    output.value.to_sat().to_string(),
+                    format!("{:?}", output.script_pubkey.classify()),
+                ]
+            })
+            .collect();
+
+        format!(
+            "{}\n{}",
+            render_table(&["INPUT", "OUTPOINT", "SEQUENCE"], &input_rows),
+            render_table(&["OUTPUT", "VALUE", "TYPE"], &output_rows)
+        )
+    }
+
+    // Output indices split into (dust, non-dust)
+    pub fn partition_dust(&self, dust_feerate: u64) -> (Vec<usize>, Vec<usize>) {
+        (0..self.outputs.len()).partition(|i| self.outputs[*i].is_dust(dust_feerate))
+    }
+
+    pub fn has_duplicate_outputs(&self) -> bool {
+        let mut seen = BTreeSet::new();
+        !self
+            .outputs
+            .iter()
+            .all(|output| seen.insert((output.value, &output.script_pubkey.bytes)))
+    }
+
+    // Best-effort type of the script each input spends, inferred from its
+    // scriptSig and witness since prevout scripts aren't on the wire
+    pub fn input_script_types(&self) -> Vec<ScriptType> {
+        let empty = Witness::default();
+        self.inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                let witness = self.witnesses.get(i).unwrap_or(&empty);
+                let pushes: Option<Vec<&[u8]>> = input
+                    .script_sig
+                    .instructions()
+                    .map(|instruction| match instruction {
+                        Ok(Instruction::Push { data, .. }) => Some(data),
+                        _ => None,
+                    })
+                    .collect();
+                let Some(pushes) = pushes else {
+                    return ScriptType::NonStandard;
+                };
+
+                match (pushes.as_slice(), witness.items.as_slice()) {
+                    ([], [_, pubkey]) if pubkey.len() == 33 => ScriptType::P2WPKH,
+                    ([], [signature]) if matches!(signature.len(), 64 | 65) => ScriptType::P2TR,
+                    ([], [_, ..]) => ScriptType::P2WSH,
+                    ([_, ..], _) if !witness.is_empty() => ScriptType::P2SH,
+                    ([_, pubkey], []) if matches!(pubkey.len(), 33 | 65) => ScriptType::P2PKH,
+                    ([signature], []) if signature.first() == Some(&0x30) => ScriptType::P2PK,
+                    ([.., redeem_script], [])
+                        if Script::new(redeem_script.to_vec()).classify()
+                            != ScriptType::NonStandard =>
+                    {
+                        ScriptType::P2SH
+                    }
+                    _ => ScriptType::NonStandard,
+                }
+            })
+            .collect()
+    }
+
+    // True when all inputs share one script type and exactly one output has
+    // that same type, which likely marks it as change
+    pub fn change_matches_input_type(&self) -> bool {
+        let input_types = self.input_script_types();
+        let Some(first) = input_types.first() else {
+            return false;
+        };
+        if *first == ScriptType::NonStandard || input_types.iter().any(|t| t != first) {
+            return false;
+        }
+        self.output_size_histogram().get(first) == Some(&1)
+    }
+
+    pub fn output_size_histogram(&self) -> BTreeMap<ScriptType, usize> {
+        let mut histogram = BTreeMap::new();
+        for output in &self.outputs {
+            *histogram
+                .entry(output.script_pubkey.classify())
+                .or_insert(0) += 1;
+        }
+        histogram
+    }
+}
+
+// Parses each non-blank line of a dump as one hex-encoded transaction,
+// keeping a result per line so one bad line doesn't sink the batch
+pub fn parse_hex_lines(input: &str) -> Vec<Result<BitcoinTransaction, BitcoinError>> {
+    input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(BitcoinTransaction::from_hex)
+        .collect()
+}
+
+// Every spend chain leaving `start`, following transactions in `graph` that
+// spend an output of the previous one, for at most `max_depth` hops. Each
+// path begins with `start`; transactions already on a path are not revisited.
+#[cfg(feature = "std")]
+pub fn taint_paths(
+    start: Txid,
+    graph: &HashMap<Txid, BitcoinTransaction>,
+    max_depth: usize,
+) -> Vec<Vec<Txid>> {
+    let mut children: HashMap<Txid, Vec<Txid>> = HashMap::new();
+    for (txid, tx) in graph {
+        for parent in tx.parent_txids() {
+            children.entry(parent).or_default().push(txid.clone());
+        }
+    }
+    for spenders in children.values_mut() {
+        spenders.sort_by_key(|txid| txid.0);
+    }
+
+    let mut paths = Vec::new();
+    let mut pending = vec![vec![start.clone()]];
+    while let Some(path) = pending.pop() {
+        let tip = &path[path.len() - 1];
+        let next: Vec<&Txid> = match children.get(tip) {
+            Some(spenders) if path.len() <= max_depth => spenders
+                .iter()
+                .filter(|txid| !path.contains(txid))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        if next.is_empty() {
+            if path.len() > 1 {
+                paths.push(path);
+            }
+            continue;
+        }
+        for txid in next.into_iter().rev() {
+            let mut extended = path.clone();
+            extended.push(txid.clone());
+            pending.push(extended);
+        }
+    }
+    paths
+}
+
+// Indices of transactions in `others` that spend any outpoint `tx` spends.
+// A copy of `tx` itself is not reported as a conflict.
+pub fn find_conflicts(tx: &BitcoinTransaction, others: &[BitcoinTransaction]) -> Vec<usize> {
+    let spent: BTreeSet<&OutPoint> = tx
+        .inputs
+        .iter()
+        .map(|input| &input.previous_output)
+        .collect();
+    let txid = tx.txid();
+
+    others
+        .iter()
+        .enumerate()
+        .filter(|(_, other)| {
+            other
+                .inputs
+                .iter()
+                .any(|input| spent.contains(&input.previous_output))
+                && other.txid() != txid
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+// Orders `txs` by descending feerate while keeping every parent ahead of its
+// children. `fees[i]` is the fee of `txs[i]`; missing fees count as zero.
+pub fn sort_for_block(txs: &mut Vec<BitcoinTransaction>, fees: &[u64]) {
+    let txids: Vec<Txid> = txs.iter().map(|tx| tx.txid()).collect();
+    let feerates: Vec<f64> = txs
+        .iter()
+        .enumerate()
+        .map(|(i, tx)| *fees.get(i).unwrap_or(&0) as f64 / tx.vsize() as f64)
+        .collect();
+    let parents: Vec<Vec<usize>> = txs
+        .iter()
+        .map(|tx| {
+            (0..txids.len())
+                .filter(|j| {
+                    tx.inputs
+                        .iter()
+                        .any(|input| input.previous_output.txid == txids[*j])
+                })
+                .collect()
+        })
+        .collect();
 
-        // Parse inputs
-        let mut inputs = Vec::new();
-        for _ in 0..input_count.value {
-            let (input, input_bytes) = TransactionInput::from_bytes(&bytes[consumed..])?;
-            inputs.push(input);
-            consumed += input_bytes;
+    let mut placed = vec![false; txs.len()];
+    let mut order = Vec::with_capacity(txs.len());
+    while order.len() < txs.len() {
+        // Highest-feerate transaction whose parents are all placed; ties keep
+        // the original order. A dependency cycle falls back to any remaining.
+        let ready = |i: &usize| !placed[*i] && parents[*i].iter().all(|p| placed[*p]);
+        let next = (0..txs.len())
+            .filter(ready)
+            .fold(None, |best: Option<usize>, i| match best {
+                Some(b) if feerates[b] >= feerates[i] => Some(b),
+                _ => Some(i),
+            })
+            .or_else(|| (0..txs.len()).find(|i| !placed[*i]));
+        let Some(next) = next else {
+            break;
+        };
+        placed[next] = true;
+        order.push(next);
+    }
+
+    let mut slots: Vec<Option<BitcoinTransaction>> = txs.drain(..).map(Some).collect();
+    txs.extend(order.into_iter().filter_map(|i| slots[i].take()));
+}
+
+// Combined feerate in sat/vB of a parent and a child paying for it (CPFP)
+pub fn effective_feerate(
+    parent: &BitcoinTransaction,
+    parent_fee: u64,
+    child: &BitcoinTransaction,
+    child_fee: u64,
+) -> f64 {
+    let total_vsize = parent.vsize() + child.vsize();
+    (parent_fee as f64 + child_fee as f64) / total_vsize as f64
+}
+
+// BIP125 rule 5: the transactions `replacement` conflicts with, plus every
+// descendant of theirs found in `conflicts`, number at most 100. Entries of
+// `conflicts` that neither conflict nor descend from a conflict are ignored.
+pub fn rbf_descendant_ok(
+    replacement: &BitcoinTransaction,
+    conflicts: &[BitcoinTransaction],
+) -> bool {
+    let txids: Vec<Txid> = conflicts.iter().map(BitcoinTransaction::txid).collect();
+    let mut evicted: BTreeSet<usize> = find_conflicts(replacement, conflicts).into_iter().collect();
+
+    // Pull in children of evicted transactions until nothing changes
+    loop {
+        let evicted_txids: BTreeSet<&Txid> = evicted.iter().map(|i| &txids[*i]).collect();
+        let children: Vec<usize> = (0..conflicts.len())
+            .filter(|i| !evicted.contains(i))
+            .filter(|i| {
+                conflicts[*i]
+                    .inputs
+                    .iter()
+                    .any(|input| evicted_txids.contains(&input.previous_output.txid))
+            })
+            .collect();
+        if children.is_empty() {
+            break;
         }
+        evicted.extend(children);
+    }
 
-        // Parse lock time
-        if bytes.len() < consumed + 4 {
-            return Err(BitcoinError::InsufficientBytes);
+    evicted.len() <= MAX_BIP125_REPLACEMENT_CANDIDATES
+}
+
+// Smallest absolute fee a replacement may pay under BIP125: at least the
+// original fee plus its own bandwidth at the min relay rate (rules 3 and 4),
+// and never a lower feerate than the original. Saturates at u64::MAX rather
+// than overflowing on absurd fees or feerates.
+pub fn min_rbf_replacement_fee(
+    original_fee: u64,
+    original_vsize: usize,
+    replacement_vsize: usize,
+    min_relay_feerate: u64,
+) -> u64 {
+    let bandwidth_fee =
+        original_fee.saturating_add(min_relay_feerate.saturating_mul(replacement_vsize as u64));
+    let feerate_fee = if original_vsize == 0 {
+        0
+    } else {
+        let scaled =
+            (original_fee as u128 * replacement_vsize as u128).div_ceil(original_vsize as u128);
+        u64::try_from(scaled).unwrap_or(u64::MAX)
+    };
+    bandwidth_fee.max(feerate_fee)
+}
+
+fn bip69_input_order(a: &TransactionInput, b: &TransactionInput) -> core::cmp::Ordering {
+    let a_txid = a.previous_output.txid.0.iter().rev();
+    let b_txid = b.previous_output.txid.0.iter().rev();
+    a_txid
+        .cmp(b_txid)
+        .then(a.previous_output.vout.cmp(&b.previous_output.vout))
+}
+
+// Left-aligns every column to its widest cell, one line per row
+fn render_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
         }
-        let lock_time = u32::from_le_bytes([
-            bytes[consumed],
-            bytes[consumed + 1],
-            bytes[consumed + 2],
-            bytes[consumed + 3],
-        ]);
-        consumed += 4;
+    }
 
-        Ok((
-            BitcoinTransaction::new(version, inputs, lock_time),
-            consumed,
-        ))
+    let mut table = String::new();
+    let header_row: Vec<String> = headers.iter().map(|header| header.to_string()).collect();
+    for row in core::iter::once(&header_row).chain(rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+// Smallest possible encodings: outpoint + empty scriptSig + sequence, and
+// value + empty scriptPubKey
+const MIN_INPUT_SIZE: usize = 41;
+const MIN_OUTPUT_SIZE: usize = 9;
+
+// Rejects a declared element count that could not fit in `remaining` bytes
+// even if every element were minimal
+fn check_count_fits(count: usize, min_size: usize, remaining: usize) -> Result<(), BitcoinError> {
+    let needed = count.saturating_mul(min_size);
+    if needed > remaining {
+        return Err(BitcoinError::InsufficientBytes {
+            needed,
+            got: remaining,
+        });
+    }
+    Ok(())
+}
+
+fn write_stable_json(value: &serde_json::Value, out: &mut String) {
+    match value {
+        serde_json::Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_stable_json(item, out);
+            }
+            out.push(']');
+        }
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::Value::String(key.clone()).to_string());
+                out.push(':');
+                write_stable_json(item, out);
+            }
+            out.push('}');
+        }
+        // Scalars: all numbers here are integers, so formatting is fixed
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+// SplitMix64 step: small, fast, and stable across platforms and releases
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// `len` bytes of `bytes` starting at `start`, without overflowing on
+// absurd declared lengths
+fn slice_at(bytes: &[u8], start: usize, len: usize) -> Result<&[u8], BitcoinError> {
+    match start.checked_add(len) {
+        Some(end) if end <= bytes.len() => Ok(&bytes[start..end]),
+        end => Err(BitcoinError::InsufficientBytes {
+            needed: end.unwrap_or(usize::MAX),
+            got: bytes.len(),
+        }),
+    }
+}
+
+// Runs a `consensus_encode` into a fresh Vec, which cannot fail
+fn encode_to_vec<F>(encode: F) -> Vec<u8>
+where
+    F: FnOnce(&mut Vec<u8>) -> io::Result<usize>,
+{
+    let mut bytes = Vec::new();
+    encode(&mut bytes).expect("writing to a Vec cannot fail");
+    bytes
+}
+
+// Runs a `consensus_encode` only to learn how many bytes it would write
+fn encoded_size<F>(encode: F) -> usize
+where
+    F: FnOnce(&mut io::Sink) -> io::Result<usize>,
+{
+    encode(&mut io::sink()).expect("writing to a sink cannot fail")
+}
+
+// Reads a length-prefixed payload. The buffer grows with the data actually
+// read, so a huge declared length cannot force a huge allocation.
+fn read_vec<R: Read>(reader: &mut R, len: u64) -> Result<Vec<u8>, BitcoinError> {
+    let needed = usize::try_from(len).unwrap_or(usize::MAX);
+    let mut bytes = Vec::new();
+    let mut chunk = [0u8; 4096];
+    while bytes.len() < needed {
+        let want = chunk.len().min(needed - bytes.len());
+        match reader.read(&mut chunk[..want]) {
+            Ok(0) => {
+                return Err(BitcoinError::InsufficientBytes {
+                    needed,
+                    got: bytes.len(),
+                });
+            }
+            Ok(n) => bytes.extend_from_slice(&chunk[..n]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(_) => return Err(BitcoinError::InvalidFormat),
+        }
+    }
+    Ok(bytes)
+}
+
+// Like `Read::read_exact`, but reports how far it got when the stream ends
+fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), BitcoinError> {
+    let mut got = 0;
+    while got < buf.len() {
+        match reader.read(&mut buf[got..]) {
+            Ok(0) => {
+                return Err(BitcoinError::InsufficientBytes {
+                    needed: buf.len(),
+                    got,
+                });
+            }
+            Ok(n) => got += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(_) => return Err(BitcoinError::InvalidFormat),
+        }
+    }
+    Ok(())
+}
+
+pub fn weight_to_vsize(weight: u64) -> u64 {
+    weight.div_ceil(WITNESS_SCALE_FACTOR as u64)
+}
+
+// Double SHA-256, as used for txids and most other Bitcoin hashes
+pub fn hash256(bytes: &[u8]) -> [u8; 32] {
+    let first = Sha256::digest(bytes);
+    Sha256::digest(first).into()
+}
+
+// Txid of a raw legacy (non-witness) serialization
+pub fn compute_txid(bytes: &[u8]) -> Txid {
+    Txid(hash256(bytes))
+}
+
+// (offset, byte in a, byte in b) wherever the serializations differ. Every
+// offset past the end of the shorter one is reported, whatever the longer
+// one holds there, with 0x00 standing in for the missing side; the first
+// such offset is where the lengths diverge.
+pub fn tx_byte_diff(a: &BitcoinTransaction, b: &BitcoinTransaction) -> Vec<(usize, u8, u8)> {
+    let (a, b) = (a.to_bytes(), b.to_bytes());
+    let common = a.len().min(b.len());
+    (0..a.len().max(b.len()))
+        .map(|offset| {
+            (
+                offset,
+                a.get(offset).copied().unwrap_or(0),
+                b.get(offset).copied().unwrap_or(0),
+            )
+        })
+        .filter(|(offset, old, new)| *offset >= common || old != new)
+        .collect()
+}
+
+// Light-wallet block scan: total value paid to `watch` scripts and the new
+// UTXOs carrying it, in block order. Spends of watched coins are not
+// tracked here. The total saturates, as parsed values aren't capped at
+// MAX_MONEY.
+#[cfg(feature = "std")]
+pub fn scan_block_for_scripts(
+    txs: &[BitcoinTransaction],
+    watch: &HashSet<Vec<u8>>,
+) -> (u64, Vec<OutPoint>) {
+    let mut received: u64 = 0;
+    let mut utxos = Vec::new();
+    for tx in txs {
+        for (outpoint, output) in tx.created_outputs() {
+            if watch.contains(&output.script_pubkey.bytes) {
+                received = received.saturating_add(output.value.to_sat());
+                utxos.push(outpoint);
+            }
+        }
+    }
+    (received, utxos)
+}
+
+// Every output scriptPubKey in block order, e.g. to build a filter or
+// watch set; duplicates are kept
+pub fn block_output_scripts(txs: &[BitcoinTransaction]) -> Vec<Script> {
+    txs.iter()
+        .flat_map(|tx| tx.outputs.iter())
+        .map(|output: &TxOut| output.script_pubkey.clone())
+        .collect()
+}
+
+// True when a transaction spends an output of an earlier transaction in the
+// same slice, i.e. the slice is a chained (CPFP) package
+pub fn package_is_chained(txs: &[BitcoinTransaction]) -> bool {
+    let mut created: BTreeSet<OutPoint> = BTreeSet::new();
+    for tx in txs {
+        if tx
+            .inputs
+            .iter()
+            .any(|input| created.contains(&input.previous_output))
+        {
+            return true;
+        }
+        created.extend(
+            tx.created_outputs()
+                .into_iter()
+                .map(|(outpoint, _)| outpoint),
+        );
+    }
+    false
+}
+
+// Counts the distinct unconfirmed ancestors of `tx` found in `graph`; the
+// visited set keeps the walk finite even if the graph contains a cycle
+#[cfg(feature = "std")]
+pub fn ancestor_count(tx: &BitcoinTransaction, graph: &HashMap<Txid, BitcoinTransaction>) -> usize {
+    let mut visited: HashSet<Txid> = HashSet::new();
+    let mut pending: Vec<&Txid> = tx
+        .inputs
+        .iter()
+        .map(|input| &input.previous_output.txid)
+        .collect();
+
+    while let Some(txid) = pending.pop() {
+        if let Some(parent) = graph.get(txid)
+            && visited.insert(txid.clone())
+        {
+            pending.extend(
+                parent
+                    .inputs
+                    .iter()
+                    .map(|input| &input.previous_output.txid),
+            );
+        }
+    }
+
+    visited.len()
+}
+
+// How an input will be signed, for sizing before signatures exist
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
+pub enum InputKind {
+    P2PKH,
+    P2WPKH,
+    P2SHP2WPKH,
+    // Taproot key-path spend
+    P2TR,
+}
+
+impl InputKind {
+    // (scriptSig length, serialized witness length) assuming worst-case
+    // 73-byte ECDSA signatures and 65-byte Schnorr signatures. P2PKH allows
+    // for a 65-byte uncompressed pubkey; segwit v0 policy requires
+    // compressed (33-byte) keys.
+    fn signed_sizes(&self) -> (usize, usize) {
+        match self {
+            // <sig> <pubkey>
+            InputKind::P2PKH => (1 + 73 + 1 + 65, 0),
+            // witness: count, <sig>, <pubkey>
+            InputKind::P2WPKH => (0, 1 + 1 + 73 + 1 + 33),
+            // scriptSig pushes the 22-byte witness program
+            InputKind::P2SHP2WPKH => (1 + 22, 1 + 1 + 73 + 1 + 33),
+            // witness: count, <schnorr sig + sighash byte>
+            InputKind::P2TR => (0, 1 + 1 + 65),
+        }
+    }
+}
+
+// Upper bound on the vsize of the fully signed transaction, for coin
+// selection and fee reservation before signing
+pub fn max_signed_vsize(input_kinds: &[InputKind], output_scripts: &[Script]) -> usize {
+    let mut base_size = 4 + CompactSize::new(input_kinds.len() as u64).encoded_len();
+    let mut witness_size = 0;
+    for kind in input_kinds {
+        let (script_sig_len, witness_len) = kind.signed_sizes();
+        base_size +=
+            36 + CompactSize::new(script_sig_len as u64).encoded_len() + script_sig_len + 4;
+        // Inputs without a witness still take an empty-stack byte
+        witness_size += witness_len.max(1);
+    }
+
+    base_size += CompactSize::new(output_scripts.len() as u64).encoded_len();
+    for script in output_scripts {
+        base_size += 8 + CompactSize::new(script.len() as u64).encoded_len() + script.len();
+    }
+    base_size += 4;
+
+    let has_witness = input_kinds.iter().any(|kind| kind.signed_sizes().1 > 0);
+    let weight = if has_witness {
+        // Marker and flag count as witness data
+        base_size * WITNESS_SCALE_FACTOR + 2 + witness_size
+    } else {
+        base_size * WITNESS_SCALE_FACTOR
+    };
+    weight_to_vsize(weight as u64) as usize
+}
+
+// Final: opts out of locktime and replacement
+pub const SEQUENCE_FINAL: u32 = 0xFFFFFFFF;
+// Highest sequence that signals BIP125 replaceability
+pub const SEQUENCE_RBF: u32 = 0xFFFFFFFD;
+
+// The BIP143 midstates shared by every input signed with SIGHASH_ALL:
+// hashes of all prevouts, all sequences and all outputs
+#[derive(Debug, Clone)]
+pub struct SighashCache<'a> {
+    tx: &'a BitcoinTransaction,
+    hash_prevouts: [u8; 32],
+    hash_sequence: [u8; 32],
+    hash_outputs: [u8; 32],
+}
+
+impl<'a> SighashCache<'a> {
+    pub fn new(tx: &'a BitcoinTransaction) -> Self {
+        let mut prevouts = Vec::new();
+        let mut sequences = Vec::new();
+        for input in &tx.inputs {
+            prevouts.extend_from_slice(&input.previous_output.to_bytes());
+            sequences.extend_from_slice(&input.sequence.to_u32().to_le_bytes());
+        }
+        let mut outputs = Vec::new();
+        for output in &tx.outputs {
+            outputs.extend_from_slice(&output.to_bytes());
+        }
+
+        SighashCache {
+            tx,
+            hash_prevouts: hash256(&prevouts),
+            hash_sequence: hash256(&sequences),
+            hash_outputs: hash256(&outputs),
+        }
+    }
+
+    pub fn segwit_sighash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        value: u64,
+        sighash_type: u32,
+    ) -> Result<[u8; 32], BitcoinError> {
+        let preimage = self.bip143_preimage(input_index, script_code, value, sighash_type)?;
+        Ok(hash256(&preimage))
+    }
+
+    fn bip143_preimage(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        value: u64,
+        sighash_type: u32,
+    ) -> Result<Vec<u8>, BitcoinError> {
+        let tx = self.tx;
+        let input = tx
+            .inputs
+            .get(input_index)
+            .ok_or(BitcoinError::InvalidFormat)?;
+        let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+        let base_type = sighash_type & 0x1F;
+
+        let hash_prevouts = if anyone_can_pay {
+            [0u8; 32]
+        } else {
+            self.hash_prevouts
+        };
+        let hash_sequence =
+            if anyone_can_pay || base_type == SIGHASH_SINGLE || base_type == SIGHASH_NONE {
+                [0u8; 32]
+            } else {
+                self.hash_sequence
+            };
+        let hash_outputs = if base_type != SIGHASH_SINGLE && base_type != SIGHASH_NONE {
+            self.hash_outputs
+        } else if base_type == SIGHASH_SINGLE && input_index < tx.outputs.len() {
+            hash256(&tx.outputs[input_index].to_bytes())
+        } else {
+            [0u8; 32]
+        };
+
+        let mut preimage = Vec::new();
+        preimage.extend_from_slice(&tx.version.to_le_bytes());
+        preimage.extend_from_slice(&hash_prevouts);
+        preimage.extend_from_slice(&hash_sequence);
+        preimage.extend_from_slice(&input.previous_output.to_bytes());
+        preimage.extend_from_slice(&script_code.to_bytes());
+        preimage.extend_from_slice(&value.to_le_bytes());
+        preimage.extend_from_slice(&input.sequence.to_u32().to_le_bytes());
+        preimage.extend_from_slice(&hash_outputs);
+        preimage.extend_from_slice(&tx.lock_time.to_u32().to_le_bytes());
+        preimage.extend_from_slice(&sighash_type.to_le_bytes());
+        Ok(preimage)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TransactionBuilder {
+    tx: BitcoinTransaction,
+}
+
+impl TransactionBuilder {
+    pub fn new(version: u32) -> Self {
+        TransactionBuilder {
+            tx: BitcoinTransaction::new(version, Vec::new(), Vec::new(), 0),
+        }
     }
+
+    pub fn add_input(
+        mut self,
+        previous_output: OutPoint,
+        script_sig: Script,
+        sequence: u32,
+    ) -> Self {
+        self.tx
+            .inputs
+            .push(TransactionInput::new(previous_output, script_sig, sequence));
+        self
+    }
+
+    pub fn add_final_input(self, previous_output: OutPoint, script_sig: Script) -> Self {
+        self.add_input(previous_output, script_sig, SEQUENCE_FINAL)
+    }
+
+    pub fn add_rbf_input(self, previous_output: OutPoint, script_sig: Script) -> Self {
+        self.add_input(previous_output, script_sig, SEQUENCE_RBF)
+    }
+
+    pub fn add_output(mut self, value: u64, script_pubkey: Script) -> Self {
+        self.tx
+            .outputs
+            .push(TransactionOutput::new(value, script_pubkey));
+        self
+    }
+
+    pub fn lock_time(mut self, lock_time: u32) -> Self {
+        self.tx.lock_time = LockTime::from_u32(lock_time);
+        self
+    }
+
+    pub fn build(self) -> BitcoinTransaction {
+        self.tx
+    }
+}
+
+// Scripts are shown as hex, or as ASM with the alternate flag (`{:#}`).
+// Scripts that fail to disassemble fall back to hex.
+fn script_text(script: &Script, f: &fmt::Formatter<'_>) -> String {
+    if f.alternate()
+        && let Ok(asm) = script.disassemble()
+    {
+        return asm;
+    }
+    hex::encode(&script.bytes)
 }
 
 impl fmt::Display for BitcoinTransaction {
@@ -319,7 +3264,7 @@ impl fmt::Display for BitcoinTransaction {
             writeln!(
                 f,
                 "      Previous Output Txid: {}",
-                hex::encode(input.previous_output.txid.0)
+                input.previous_output.txid
             )?;
             writeln!(
                 f,
@@ -331,16 +3276,330 @@ impl fmt::Display for BitcoinTransaction {
                 "      Script Sig Length: {}",
                 input.script_sig.bytes.len()
             )?;
+            writeln!(f, "      Script Sig: {}", script_text(&input.script_sig, f))?;
+            writeln!(f, "      Sequence: 0x{:08x}", input.sequence.to_u32())?;
+        }
+
+        writeln!(f, "  Outputs: {}", self.outputs.len())?;
+
+        for (i, output) in self.outputs.iter().enumerate() {
+            writeln!(f, "    Output {}:", i)?;
+            writeln!(f, "      Value: {}", output.value.to_sat())?;
             writeln!(
                 f,
-                "      Script Sig: {}",
-                hex::encode(&input.script_sig.bytes)
+                "      Script Pubkey Length: {}",
+                output.script_pubkey.bytes.len()
             )?;
-            writeln!(f, "      Sequence: 0x{:08x}", input.sequence)?;
+            if f.alternate() {
+                writeln!(
+                    f,
+                    "      Script Pubkey: {}",
+                    script_text(&output.script_pubkey, f)
+                )?;
+            }
         }
 
-        write!(f, "  Lock Time: {}", self.lock_time)?;
+        write!(f, "  Lock Time: {}", self.lock_time.to_u32())?;
 
         Ok(())
     }
 }
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct RejectMessage {
+    pub command: String,
+    pub code: u8,
+    pub reason: String,
+    pub data: Vec<u8>,
+}
+
+impl RejectMessage {
+    pub fn new(command: String, code: u8, reason: String, data: Vec<u8>) -> Self {
+        RejectMessage {
+            command,
+            code,
+            reason,
+            data,
+        }
+    }
+
+    // Parses a BIP61 reject payload; the trailing data (usually the rejected
+    // txid or block hash) takes whatever bytes remain
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let mut consumed = 0;
+
+        // Parse command
+        let (command, command_bytes) = read_var_str(bytes)?;
+        consumed += command_bytes;
+
+        // Parse code
+        if bytes.len() < consumed + 1 {
+            return Err(BitcoinError::InsufficientBytes {
+                needed: consumed + 1,
+                got: bytes.len(),
+            });
+        }
+        let code = bytes[consumed];
+        consumed += 1;
+
+        // Parse reason
+        let (reason, reason_bytes) = read_var_str(&bytes[consumed..])?;
+        consumed += reason_bytes;
+
+        // Remaining bytes are optional extra data
+        let data = bytes[consumed..].to_vec();
+        consumed = bytes.len();
+
+        Ok((RejectMessage::new(command, code, reason, data), consumed))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub version: u32,
+    pub prev_blockhash: [u8; 32],
+    pub merkle_root: [u8; 32],
+    pub time: u32,
+    pub bits: u32,
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    pub fn new(
+        version: u32,
+        prev_blockhash: [u8; 32],
+        merkle_root: [u8; 32],
+        time: u32,
+        bits: u32,
+        nonce: u32,
+    ) -> Self {
+        BlockHeader {
+            version,
+            prev_blockhash,
+            merkle_root,
+            time,
+            bits,
+            nonce,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(80);
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bytes.extend_from_slice(&self.prev_blockhash);
+        bytes.extend_from_slice(&self.merkle_root);
+        bytes.extend_from_slice(&self.time.to_le_bytes());
+        bytes.extend_from_slice(&self.bits.to_le_bytes());
+        bytes.extend_from_slice(&self.nonce.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        if bytes.len() < 80 {
+            return Err(BitcoinError::InsufficientBytes {
+                needed: 80,
+                got: bytes.len(),
+            });
+        }
+
+        let read_u32 = |at: usize| {
+            u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+        };
+
+        let mut prev_blockhash = [0u8; 32];
+        prev_blockhash.copy_from_slice(&bytes[4..36]);
+
+        let mut merkle_root = [0u8; 32];
+        merkle_root.copy_from_slice(&bytes[36..68]);
+
+        Ok((
+            BlockHeader::new(
+                read_u32(0),
+                prev_blockhash,
+                merkle_root,
+                read_u32(68),
+                read_u32(72),
+                read_u32(76),
+            ),
+            80,
+        ))
+    }
+
+    // Double SHA-256 of the header, in internal (little-endian) byte order
+    pub fn block_hash(&self) -> [u8; 32] {
+        hash256(&self.to_bytes())
+    }
+
+    // Leading zero bits of the hash as displayed (i.e. read from its last
+    // internal byte), a quick proxy for the work it represents
+    pub fn leading_zero_bits(&self) -> u32 {
+        let mut zeros = 0;
+        for byte in self.block_hash().iter().rev() {
+            zeros += byte.leading_zeros();
+            if *byte != 0 {
+                break;
+            }
+        }
+        zeros
+    }
+}
+
+pub const MSG_TX: u32 = 1;
+pub const MSG_BLOCK: u32 = 2;
+pub const MSG_WTX: u32 = 5;
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct InvVector {
+    pub inv_type: u32,
+    pub hash: [u8; 32],
+}
+
+impl InvVector {
+    pub fn new(inv_type: u32, hash: [u8; 32]) -> Self {
+        InvVector { inv_type, hash }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.inv_type.to_le_bytes());
+        bytes.extend_from_slice(&self.hash);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        if bytes.len() < 36 {
+            return Err(BitcoinError::InsufficientBytes {
+                needed: 36,
+                got: bytes.len(),
+            });
+        }
+
+        let inv_type = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&bytes[4..36]);
+
+        Ok((InvVector::new(inv_type, hash), 36))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct NetAddr {
+    pub time: u32,
+    pub services: u64,
+    pub ip: [u8; 16],
+    pub port: u16,
+}
+
+impl NetAddr {
+    pub fn new(time: u32, services: u64, ip: [u8; 16], port: u16) -> Self {
+        NetAddr {
+            time,
+            services,
+            ip,
+            port,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.time.to_le_bytes());
+        bytes.extend_from_slice(&self.services.to_le_bytes());
+        bytes.extend_from_slice(&self.ip);
+        // Port is the one big-endian field in the p2p protocol
+        bytes.extend_from_slice(&self.port.to_be_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        if bytes.len() < 30 {
+            return Err(BitcoinError::InsufficientBytes {
+                needed: 30,
+                got: bytes.len(),
+            });
+        }
+
+        let time = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+        let mut services = [0u8; 8];
+        services.copy_from_slice(&bytes[4..12]);
+
+        let mut ip = [0u8; 16];
+        ip.copy_from_slice(&bytes[12..28]);
+
+        let port = u16::from_be_bytes([bytes[28], bytes[29]]);
+
+        Ok((
+            NetAddr::new(time, u64::from_le_bytes(services), ip, port),
+            30,
+        ))
+    }
+}
+
+// Parses the payload of an `addr` message: a CompactSize count followed by
+// that many 30-byte network addresses
+pub fn parse_addr_message(bytes: &[u8]) -> Result<Vec<NetAddr>, BitcoinError> {
+    let (count, mut consumed) = CompactSize::from_bytes(bytes)?;
+
+    let mut addrs = Vec::new();
+    for _ in 0..count.value {
+        let (addr, addr_bytes) = NetAddr::from_bytes(&bytes[consumed..])?;
+        addrs.push(addr);
+        consumed += addr_bytes;
+    }
+
+    Ok(addrs)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct MempoolEntry {
+    pub vsize: u64,
+    // Base fee in satoshis
+    pub fee: u64,
+    pub depends: Vec<Txid>,
+    pub ancestor_count: u64,
+}
+
+impl MempoolEntry {
+    // Parses one entry of `getrawmempool true` / `getmempoolentry`. Fees are
+    // read from `fees.base`, falling back to the pre-0.21 top-level `fee`.
+    #[cfg(feature = "std")]
+    pub fn from_json(value: &serde_json::Value) -> Result<MempoolEntry, BitcoinError> {
+        let vsize = value["vsize"].as_u64().ok_or(BitcoinError::InvalidFormat)?;
+        let ancestor_count = value["ancestorcount"]
+            .as_u64()
+            .ok_or(BitcoinError::InvalidFormat)?;
+
+        let fee_btc = value["fees"]["base"]
+            .as_f64()
+            .or_else(|| value["fee"].as_f64())
+            .ok_or(BitcoinError::InvalidFormat)?;
+        let fee = Amount::from_btc(fee_btc)?.to_sat();
+
+        let mut depends = Vec::new();
+        for txid in value["depends"]
+            .as_array()
+            .ok_or(BitcoinError::InvalidFormat)?
+        {
+            let txid = txid.as_str().ok_or(BitcoinError::InvalidFormat)?;
+            depends.push(Txid::from_str(txid)?);
+        }
+
+        Ok(MempoolEntry {
+            vsize,
+            fee,
+            depends,
+            ancestor_count,
+        })
+    }
+}
+
+// Reads a CompactSize-prefixed UTF-8 string
+fn read_var_str(bytes: &[u8]) -> Result<(String, usize), BitcoinError> {
+    let (length, length_bytes) = CompactSize::from_bytes(bytes)?;
+    let str_length = usize::try_from(length)?;
+
+    let value = String::from_utf8(slice_at(bytes, length_bytes, str_length)?.to_vec())
+        .map_err(|_| BitcoinError::InvalidFormat)?;
+    Ok((value, length_bytes + str_length))
+}
@@ -4,12 +4,54 @@ use rust_week_3_exercises::*;
 mod tests {
     use super::*;
 
+    // The first Bitcoin payment, Satoshi to Hal Finney in block 170
+    const BLOCK_170_TX_HEX: &str = "0100000001c997a5e56e104102fa209c6a852dd90660a20b2d9c352423edce25857fcd3704000000004847304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901ffffffff0200ca9a3b00000000434104ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac00286bee0000000043410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac00000000";
+    const BLOCK_170_TXID: &str = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+
+    // Signed native P2WPKH example from BIP143: input 0 spends a P2PK coin
+    // with a scriptSig, input 1 a P2WPKH coin with a witness
+    const BIP143_P2WPKH_TX_HEX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeeeeeac11000000";
+
+    fn block_170_tx() -> BitcoinTransaction {
+        let bytes = hex::decode(BLOCK_170_TX_HEX).unwrap();
+        BitcoinTransaction::from_bytes(&bytes).unwrap().0
+    }
+
     fn dummy_txid(val: u8) -> [u8; 32] {
         let mut txid = [0u8; 32];
         txid[31] = val;
         txid
     }
 
+    fn dummy_p2pkh(val: u8) -> Script {
+        let mut bytes = vec![0x76, 0xA9, 0x14];
+        bytes.extend_from_slice(&[val; 20]);
+        bytes.extend_from_slice(&[0x88, 0xAC]);
+        Script::new(bytes)
+    }
+
+    fn dummy_p2wpkh(val: u8) -> Script {
+        let mut bytes = vec![0x00, 0x14];
+        bytes.extend_from_slice(&[val; 20]);
+        Script::new(bytes)
+    }
+
+    // Unsigned input spending `txid:vout` with a final sequence
+    fn dummy_input(txid: [u8; 32], vout: u32) -> TransactionInput {
+        TransactionInput::new(OutPoint::new(txid, vout), Script::new(vec![]), 0xFFFFFFFF)
+    }
+
+    // One-in, one-out transaction spending output 0 of `prev_txid`; `tag`
+    // picks the payee so otherwise identical spends get distinct txids
+    fn spend(prev_txid: [u8; 32], tag: u8) -> BitcoinTransaction {
+        BitcoinTransaction::new(
+            2,
+            vec![dummy_input(prev_txid, 0)],
+            vec![TransactionOutput::new(1_000, dummy_p2wpkh(tag))],
+            0,
+        )
+    }
+
     #[test]
     fn test_compact_size_serialization() {
         let tests = vec![
@@ -34,6 +76,53 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_compact_size_boundary_roundtrip() {
+        for value in [
+            252u64,
+            253,
+            0xFFFF,
+            0x1_0000,
+            0xFFFF_FFFF,
+            0x1_0000_0000,
+            u64::MAX,
+        ] {
+            let bytes = CompactSize::new(value).to_bytes();
+            let (decoded, consumed) = CompactSize::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded.value, value);
+            assert_eq!(consumed, bytes.len());
+        }
+    }
+
+    #[test]
+    fn test_compact_size_rejects_overlong_encodings() {
+        let overlong: Vec<Vec<u8>> = vec![
+            // 1 and 252 in the 3-byte form
+            vec![0xFD, 0x01, 0x00],
+            vec![0xFD, 0xFC, 0x00],
+            // 0xFFFF in the 5-byte form
+            vec![0xFE, 0xFF, 0xFF, 0x00, 0x00],
+            vec![0xFE, 0x00, 0x00, 0x00, 0x00],
+            // 0xFFFFFFFF in the 9-byte form
+            vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00],
+            vec![0xFF, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        ];
+        for bytes in overlong {
+            assert_eq!(
+                CompactSize::from_bytes(&bytes),
+                Err(BitcoinError::InvalidFormat),
+                "{:02x?}",
+                bytes
+            );
+        }
+
+        // An overlong script length poisons the enclosing parse too
+        assert_eq!(
+            Script::from_bytes(&[0xFD, 0x01, 0x00, 0x51]),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
     #[test]
     fn test_outpoint_roundtrip() {
         let txid = dummy_txid(0xCC);
@@ -66,6 +155,57 @@ mod tests {
         assert_eq!(consumed, bytes.len());
     }
 
+    #[test]
+    fn test_tx_output_roundtrip() {
+        let output = TransactionOutput::new(50_000, dummy_p2pkh(0xAB));
+        let bytes = output.to_bytes();
+        assert_eq!(&bytes[..8], &50_000u64.to_le_bytes());
+        assert_eq!(bytes[8], 25);
+        let (parsed, consumed) = TransactionOutput::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, output);
+        assert_eq!(consumed, bytes.len());
+
+        assert_eq!(
+            TransactionOutput::from_bytes(&bytes[..7]),
+            Err(BitcoinError::InsufficientBytes { needed: 8, got: 7 })
+        );
+        assert_eq!(
+            TransactionOutput::from_bytes(&bytes[..20]),
+            Err(BitcoinError::InsufficientBytes {
+                needed: 26,
+                got: 12
+            })
+        );
+    }
+
+    #[test]
+    fn test_bitcoin_tx_with_outputs_roundtrip() {
+        let tx = BitcoinTransaction::new(
+            1,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![0x01, 0x02]),
+                0xFFFFFFFF,
+            )],
+            vec![
+                TransactionOutput::new(1_000, dummy_p2pkh(1)),
+                TransactionOutput::new(2_000, dummy_p2wpkh(2)),
+            ],
+            0,
+        );
+        let bytes = tx.to_bytes();
+        let (parsed, consumed) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, tx);
+        assert_eq!(consumed, bytes.len());
+
+        // Outputs sit between the inputs and the lock time
+        assert_eq!(bytes[4 + 1 + 43], 2);
+        assert_eq!(&bytes[bytes.len() - 4..], &[0, 0, 0, 0]);
+
+        // A real mainnet transaction round-trips byte for byte
+        assert_eq!(hex::encode(block_170_tx().to_bytes()), BLOCK_170_TX_HEX);
+    }
+
     #[test]
     fn test_bitcoin_tx_roundtrip() {
         let inputs = vec![TransactionInput::new(
@@ -73,7 +213,7 @@ mod tests {
             Script::new(vec![0x01, 0x02]),
             0xFFFFFFFF,
         )];
-        let tx = BitcoinTransaction::new(2, inputs.clone(), 1000);
+        let tx = BitcoinTransaction::new(2, inputs.clone(), vec![], 1000);
         let bytes = tx.to_bytes();
         let (parsed, consumed) = BitcoinTransaction::from_bytes(&bytes).unwrap();
         assert_eq!(parsed, tx);
@@ -87,7 +227,7 @@ mod tests {
             Script::new(vec![0xDE, 0xAD, 0xBE, 0xEF]),
             0xABCDEF01,
         );
-        let tx = BitcoinTransaction::new(1, vec![input], 999);
+        let tx = BitcoinTransaction::new(1, vec![input], vec![], 999);
 
         let json = serde_json::to_string_pretty(&tx).unwrap();
         let parsed: BitcoinTransaction = serde_json::from_str(&json).unwrap();
@@ -104,10 +244,2672 @@ mod tests {
             Script::new(vec![0x01, 0x02, 0x03]),
             0xFFFFFFFF,
         );
-        let tx = BitcoinTransaction::new(1, vec![input], 0);
+        let tx = BitcoinTransaction::new(1, vec![input], vec![], 0);
         let output = format!("{}", tx);
         assert!(output.contains("Version: 1"));
         assert!(output.contains("Lock Time: 0"));
         assert!(output.contains("Previous Output Vout: 7"));
     }
+
+    #[test]
+    fn test_bitcoin_transaction_display_outputs() {
+        let output = format!("{}", block_170_tx());
+        assert!(output.contains("Outputs: 2"));
+        assert!(output.contains("Output 0:"));
+        assert!(output.contains("Value: 1000000000"));
+        assert!(output.contains("Value: 4000000000"));
+        assert!(output.contains("Script Pubkey Length: 67"));
+    }
+
+    #[test]
+    fn test_package_is_chained() {
+        let parent = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![0x01]),
+                0xFFFFFFFF,
+            )],
+            vec![TransactionOutput::new(1_000, dummy_p2wpkh(1))],
+            0,
+        );
+        let child = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(parent.txid().0, 0),
+                Script::new(vec![0x02]),
+                0xFFFFFFFF,
+            )],
+            vec![],
+            0,
+        );
+        // Right txid, but the parent has no output 1
+        let bad_vout = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(parent.txid().0, 1),
+                Script::new(vec![0x04]),
+                0xFFFFFFFF,
+            )],
+            vec![],
+            0,
+        );
+        let unrelated = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(2), 0),
+                Script::new(vec![0x03]),
+                0xFFFFFFFF,
+            )],
+            vec![],
+            0,
+        );
+
+        assert!(package_is_chained(&[parent.clone(), child.clone()]));
+        assert!(!package_is_chained(&[child, parent.clone()]));
+        assert!(!package_is_chained(&[parent.clone(), unrelated]));
+        assert!(!package_is_chained(&[parent, bad_vout]));
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn test_to_smallvec_matches_to_bytes() {
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![0x01, 0x02]),
+                0xFFFFFFFF,
+            )],
+            vec![TransactionOutput::new(50_000, dummy_p2wpkh(2))],
+            1000,
+        );
+        let small = tx.to_smallvec();
+        assert!(!small.spilled());
+        assert_eq!(small.as_slice(), tx.to_bytes().as_slice());
+    }
+
+    #[test]
+    fn test_reject_message_parse() {
+        let mut bytes = vec![0x02];
+        bytes.extend_from_slice(b"tx");
+        bytes.push(0x42);
+        bytes.push(0x0C);
+        bytes.extend_from_slice(b"insufficient");
+        bytes.extend_from_slice(&dummy_txid(0x11));
+
+        let (reject, consumed) = RejectMessage::from_bytes(&bytes).unwrap();
+        assert_eq!(reject.command, "tx");
+        assert_eq!(reject.code, 0x42);
+        assert_eq!(reject.reason, "insufficient");
+        assert_eq!(reject.data, dummy_txid(0x11).to_vec());
+        assert_eq!(consumed, bytes.len());
+
+        assert_eq!(
+            RejectMessage::from_bytes(&[0x02, b't']),
+            Err(BitcoinError::InsufficientBytes { needed: 3, got: 2 })
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_ancestor_count() {
+        use std::collections::HashMap;
+
+        // confirmed -> grandparent -> parent -> child
+        let grandparent = spend(dummy_txid(9), 1);
+        let parent = spend(grandparent.txid().0, 2);
+        let child = spend(parent.txid().0, 3);
+
+        let mut graph = HashMap::new();
+        graph.insert(grandparent.txid(), grandparent.clone());
+        graph.insert(parent.txid(), parent.clone());
+
+        assert_eq!(ancestor_count(&child, &graph), 2);
+        assert_eq!(ancestor_count(&parent, &graph), 1);
+        assert_eq!(ancestor_count(&grandparent, &graph), 0);
+    }
+
+    #[test]
+    fn test_output_size_histogram() {
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![
+                TransactionOutput::new(1_000, dummy_p2pkh(1)),
+                TransactionOutput::new(2_000, dummy_p2wpkh(2)),
+                TransactionOutput::new(3_000, dummy_p2wpkh(3)),
+            ],
+            0,
+        );
+        let histogram = tx.output_size_histogram();
+        assert_eq!(histogram.get(&ScriptType::P2PKH), Some(&1));
+        assert_eq!(histogram.get(&ScriptType::P2WPKH), Some(&2));
+        assert_eq!(histogram.get(&ScriptType::P2SH), None);
+        assert_eq!(histogram.len(), 2);
+    }
+
+    #[test]
+    fn test_segwit_tx_roundtrip() {
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![]),
+                0xFFFFFFFD,
+            )],
+            vec![TransactionOutput::new(10_000, dummy_p2wpkh(1))],
+            0,
+        )
+        .with_witnesses(vec![Witness::new(vec![vec![0x30; 71], vec![0x02; 33]])]);
+
+        let bytes = tx.to_bytes();
+        assert_eq!(&bytes[4..6], &[0x00, 0x01]);
+        let (parsed, consumed) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, tx);
+        assert_eq!(consumed, bytes.len());
+        assert!(tx.to_bytes_legacy().len() < bytes.len());
+    }
+
+    #[test]
+    fn test_sorted_bip69_keeps_witnesses_with_inputs() {
+        let mut high = dummy_txid(0);
+        high[31] = 0x02;
+        let mut low = dummy_txid(0);
+        low[31] = 0x01;
+        // Display order is reversed, so the last internal byte is most significant
+        low[0] = 0xFF;
+
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(high, 0), dummy_input(low, 1)],
+            vec![
+                TransactionOutput::new(5_000, dummy_p2wpkh(2)),
+                TransactionOutput::new(5_000, dummy_p2wpkh(1)),
+                TransactionOutput::new(1_000, dummy_p2pkh(9)),
+            ],
+            0,
+        )
+        .with_witnesses(vec![
+            Witness::new(vec![vec![0xAA]]),
+            Witness::new(vec![vec![0xBB]]),
+        ]);
+
+        let sorted = tx.sorted_bip69();
+        assert_eq!(sorted.inputs[0].previous_output.txid.0, low);
+        assert_eq!(sorted.inputs[1].previous_output.txid.0, high);
+        assert_eq!(sorted.witnesses[0].items, vec![vec![0xBB]]);
+        assert_eq!(sorted.witnesses[1].items, vec![vec![0xAA]]);
+        assert_eq!(sorted.outputs[0].value.to_sat(), 1_000);
+        assert_eq!(sorted.outputs[1].script_pubkey, dummy_p2wpkh(1));
+        assert_eq!(sorted.outputs[2].script_pubkey, dummy_p2wpkh(2));
+
+        // The original is left untouched
+        assert_eq!(tx.inputs[0].previous_output.txid.0, high);
+    }
+
+    #[test]
+    fn test_has_duplicate_outputs() {
+        let input = dummy_input(dummy_txid(1), 0);
+        let duplicated = BitcoinTransaction::new(
+            2,
+            vec![input.clone()],
+            vec![
+                TransactionOutput::new(1_000, dummy_p2wpkh(1)),
+                TransactionOutput::new(1_000, dummy_p2wpkh(1)),
+            ],
+            0,
+        );
+        let distinct = BitcoinTransaction::new(
+            2,
+            vec![input],
+            vec![
+                TransactionOutput::new(1_000, dummy_p2wpkh(1)),
+                TransactionOutput::new(1_000, dummy_p2wpkh(2)),
+                TransactionOutput::new(2_000, dummy_p2wpkh(1)),
+            ],
+            0,
+        );
+        assert!(duplicated.has_duplicate_outputs());
+        assert!(!distinct.has_duplicate_outputs());
+    }
+
+    #[test]
+    fn test_witness_byte_ratio() {
+        let legacy = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![TransactionOutput::new(10_000, dummy_p2wpkh(1))],
+            0,
+        );
+        assert_eq!(legacy.witness_byte_ratio(), 0.0);
+
+        // 82 base bytes plus marker/flag and a 72-byte signature + 33-byte pubkey
+        let segwit = legacy
+            .clone()
+            .with_witnesses(vec![Witness::new(vec![vec![0x30; 72], vec![0x02; 33]])]);
+        let ratio = segwit.witness_byte_ratio();
+        assert_eq!(segwit.to_bytes_legacy().len(), 82);
+        assert!((ratio - 110.0 / 192.0).abs() < 1e-9);
+        assert!(ratio > 0.5 && ratio < 0.6);
+    }
+
+    #[test]
+    fn test_parse_addr_message() {
+        let mut ipv4 = [0u8; 16];
+        ipv4[10] = 0xFF;
+        ipv4[11] = 0xFF;
+        ipv4[12..].copy_from_slice(&[127, 0, 0, 1]);
+        let first = NetAddr::new(1_700_000_000, 1, ipv4, 8333);
+        let second = NetAddr::new(1_700_000_600, 0x409, [0x20; 16], 18333);
+
+        let mut bytes = vec![0x02];
+        bytes.extend_from_slice(&first.to_bytes());
+        bytes.extend_from_slice(&second.to_bytes());
+        assert_eq!(&bytes[29..31], &[0x20, 0x8D]);
+
+        let addrs = parse_addr_message(&bytes).unwrap();
+        assert_eq!(addrs, vec![first, second]);
+
+        assert_eq!(
+            parse_addr_message(&bytes[..40]),
+            Err(BitcoinError::InsufficientBytes { needed: 30, got: 9 })
+        );
+    }
+
+    #[test]
+    fn test_effective_feerate() {
+        let parent = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![TransactionOutput::new(10_000, dummy_p2wpkh(1))],
+            0,
+        );
+        let child = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(parent.txid().0, 0)],
+            vec![TransactionOutput::new(5_000, dummy_p2wpkh(2))],
+            0,
+        );
+        assert_eq!(parent.vsize(), 82);
+        assert_eq!(child.vsize(), 82);
+
+        // 82 sat parent (1 sat/vB) bumped by a 1558 sat child (19 sat/vB)
+        let feerate = effective_feerate(&parent, 82, &child, 1_558);
+        assert!((feerate - 10.0).abs() < 1e-9);
+
+        // Fees whose sum overflows u64 must not panic
+        let huge = effective_feerate(&parent, u64::MAX, &child, u64::MAX);
+        assert!(huge.is_finite() && huge > u64::MAX as f64 / 164.0);
+    }
+
+    #[test]
+    fn test_witness_p2wpkh_parts() {
+        let signature = vec![0x30; 71];
+        let pubkey = vec![0x02; 33];
+        let witness = Witness::new(vec![signature.clone(), pubkey.clone()]);
+        assert_eq!(witness.p2wpkh_parts(), Some((signature.clone(), pubkey)));
+
+        assert_eq!(Witness::new(vec![signature.clone()]).p2wpkh_parts(), None);
+        assert_eq!(
+            Witness::new(vec![signature, vec![0x51]]).p2wpkh_parts(),
+            None
+        );
+        assert_eq!(Witness::default().p2wpkh_parts(), None);
+    }
+
+    #[test]
+    fn test_min_rbf_replacement_fee() {
+        // 141 vB original at 2 sat/vB, same-size replacement at 1 sat/vB relay
+        assert_eq!(min_rbf_replacement_fee(282, 141, 141, 1), 423);
+        // A larger replacement pays for its own extra bandwidth
+        assert_eq!(min_rbf_replacement_fee(282, 141, 200, 1), 482);
+        // A high-feerate original dominates the bandwidth rule
+        assert_eq!(min_rbf_replacement_fee(10_000, 100, 200, 1), 20_000);
+        // Absurd user-supplied feerates and fees saturate instead of panicking
+        assert_eq!(min_rbf_replacement_fee(282, 141, 141, u64::MAX), u64::MAX);
+        assert_eq!(min_rbf_replacement_fee(u64::MAX, 1, 2, 1), u64::MAX);
+        assert_eq!(min_rbf_replacement_fee(u64::MAX / 2, 2, 4, 0), u64::MAX - 1);
+    }
+
+    #[test]
+    fn test_transaction_summary() {
+        let summary = block_170_tx().summary();
+        assert_eq!(summary.txid, BLOCK_170_TXID);
+        assert_eq!(summary.version, 1);
+        assert_eq!(summary.input_count, 1);
+        assert_eq!(summary.output_count, 2);
+        assert_eq!(summary.total_out, 5_000_000_000);
+        assert_eq!(summary.vsize, 275);
+        assert!(!summary.is_segwit);
+        assert!(!summary.is_coinbase);
+
+        let json = serde_json::to_string(&summary).unwrap();
+        assert!(json.contains(BLOCK_170_TXID));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_owned_outputs() {
+        use std::collections::HashSet;
+
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![
+                TransactionOutput::new(1_000, dummy_p2wpkh(1)),
+                TransactionOutput::new(2_000, dummy_p2wpkh(2)),
+                TransactionOutput::new(3_000, dummy_p2pkh(3)),
+            ],
+            0,
+        );
+        let mut mine = HashSet::new();
+        mine.insert(dummy_p2wpkh(2).bytes);
+        mine.insert(dummy_p2pkh(4).bytes);
+
+        assert_eq!(tx.owned_outputs(&mine), vec![(1, 2_000)]);
+        assert!(tx.owned_outputs(&HashSet::new()).is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_spends_from() {
+        use std::collections::HashSet;
+
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0), dummy_input(dummy_txid(2), 1)],
+            vec![TransactionOutput::new(1_000, dummy_p2wpkh(1))],
+            0,
+        );
+        let mut mine = HashSet::new();
+        mine.insert(OutPoint::new(dummy_txid(2), 1));
+        mine.insert(OutPoint::new(dummy_txid(1), 1));
+
+        assert_eq!(tx.spends_from(&mine), vec![1]);
+    }
+
+    #[test]
+    fn test_compact_storage_roundtrip() {
+        let first = block_170_tx();
+        let second = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![TransactionOutput::new(1_000, dummy_p2wpkh(1))],
+            0,
+        )
+        .with_witnesses(vec![Witness::new(vec![vec![0x01; 64]])]);
+
+        let mut log = first.to_compact_storage();
+        assert_eq!(&log[..4], &275u32.to_le_bytes());
+        log.extend_from_slice(&second.to_compact_storage());
+
+        let mut reader = &log[..];
+        assert_eq!(
+            BitcoinTransaction::from_compact_storage_stream(&mut reader).unwrap(),
+            first
+        );
+        assert_eq!(
+            BitcoinTransaction::from_compact_storage_stream(&mut reader).unwrap(),
+            second
+        );
+        assert_eq!(
+            BitcoinTransaction::from_compact_storage_stream(&mut reader),
+            Err(BitcoinError::InsufficientBytes { needed: 4, got: 0 })
+        );
+    }
+
+    #[test]
+    fn test_compact_storage_stream_huge_length() {
+        // A corrupt 4 GiB length header must fail on the short stream
+        // instead of allocating the whole record up front
+        let mut log = u32::MAX.to_le_bytes().to_vec();
+        log.extend_from_slice(&[0xAB; 3]);
+        assert!(matches!(
+            BitcoinTransaction::from_compact_storage_stream(&mut &log[..]),
+            Err(BitcoinError::InsufficientBytes { got: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_is_consolidation() {
+        let inputs: Vec<TransactionInput> =
+            (0..20).map(|i| dummy_input(dummy_txid(i), 0)).collect();
+        let tx = BitcoinTransaction::new(
+            2,
+            inputs.clone(),
+            vec![TransactionOutput::new(1_000_000, dummy_p2wpkh(1))],
+            0,
+        );
+        assert!(tx.is_consolidation());
+        assert!(!tx.is_consolidation_with(25, 2));
+
+        let payment = BitcoinTransaction::new(
+            2,
+            inputs[..2].to_vec(),
+            vec![TransactionOutput::new(1_000_000, dummy_p2wpkh(1))],
+            0,
+        );
+        assert!(!payment.is_consolidation());
+    }
+
+    #[test]
+    fn test_exceeds_standard_weight() {
+        let with_script_len = |len: usize| {
+            BitcoinTransaction::new(
+                2,
+                vec![dummy_input(dummy_txid(1), 0)],
+                vec![TransactionOutput::new(0, Script::new(vec![0x6A; len]))],
+                0,
+            )
+        };
+
+        let at_limit = with_script_len(99_936);
+        assert_eq!(at_limit.weight(), MAX_STANDARD_TX_WEIGHT);
+        assert!(!at_limit.exceeds_standard_weight());
+
+        let over_limit = with_script_len(99_937);
+        assert_eq!(over_limit.weight(), MAX_STANDARD_TX_WEIGHT + 4);
+        assert!(over_limit.exceeds_standard_weight());
+    }
+
+    #[test]
+    fn test_validate_segwit_scriptsigs() {
+        let with_script_sig = |script_sig: Vec<u8>| {
+            BitcoinTransaction::new(
+                2,
+                vec![TransactionInput::new(
+                    OutPoint::new(dummy_txid(1), 0),
+                    Script::new(script_sig),
+                    0xFFFFFFFF,
+                )],
+                vec![TransactionOutput::new(1_000, dummy_p2wpkh(1))],
+                0,
+            )
+            .with_witnesses(vec![Witness::new(vec![vec![0x30; 71], vec![0x02; 33]])])
+        };
+
+        // Native segwit
+        assert_eq!(with_script_sig(vec![]).validate_segwit_scriptsigs(), Ok(()));
+
+        // Nested segwit: a single push of the P2WPKH program
+        let mut nested = vec![0x16];
+        nested.extend_from_slice(&dummy_p2wpkh(7).bytes);
+        assert_eq!(with_script_sig(nested).validate_segwit_scriptsigs(), Ok(()));
+
+        // Signature-style scriptSig alongside a witness is malformed
+        let mut legacy_style = vec![0x47];
+        legacy_style.extend_from_slice(&[0x30; 71]);
+        assert_eq!(
+            with_script_sig(legacy_style).validate_segwit_scriptsigs(),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_parent_txids() {
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![
+                dummy_input(dummy_txid(1), 0),
+                dummy_input(dummy_txid(2), 0),
+                dummy_input(dummy_txid(1), 3),
+            ],
+            vec![TransactionOutput::new(1_000, dummy_p2wpkh(1))],
+            0,
+        );
+        assert_eq!(
+            tx.parent_txids(),
+            vec![Txid(dummy_txid(1)), Txid(dummy_txid(2))]
+        );
+
+        let coinbase = BitcoinTransaction::new(
+            2,
+            vec![dummy_input([0u8; 32], 0xFFFFFFFF)],
+            vec![TransactionOutput::new(1_000, dummy_p2wpkh(1))],
+            0,
+        );
+        assert!(coinbase.parent_txids().is_empty());
+    }
+
+    #[test]
+    fn test_to_psbt() {
+        let tx = block_170_tx();
+        let psbt = tx.to_psbt();
+        assert_eq!(&psbt[..5], b"psbt\xff");
+        assert_eq!(&psbt[5..7], &[0x01, 0x00]);
+
+        let mut unsigned = tx.clone();
+        unsigned.inputs[0].script_sig = Script::new(vec![]);
+        let unsigned_bytes = unsigned.to_bytes();
+        assert_eq!(psbt[7] as usize, unsigned_bytes.len());
+        assert_eq!(
+            &psbt[8..8 + unsigned_bytes.len()],
+            unsigned_bytes.as_slice()
+        );
+
+        // Global separator plus one empty map per input and per output
+        assert_eq!(&psbt[8 + unsigned_bytes.len()..], &[0x00; 4]);
+    }
+
+    #[test]
+    fn test_psbt_roundtrip() {
+        let mut unsigned = block_170_tx();
+        unsigned.inputs[0].script_sig = Script::new(vec![]);
+
+        let psbt = block_170_tx().to_psbt();
+        assert_eq!(BitcoinTransaction::from_psbt(&psbt).unwrap(), unsigned);
+
+        let mut bad_magic = psbt.clone();
+        bad_magic[4] = 0x00;
+        assert_eq!(
+            BitcoinTransaction::from_psbt(&bad_magic),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert_eq!(
+            BitcoinTransaction::from_psbt(&psbt[..20]),
+            Err(BitcoinError::InsufficientBytes {
+                needed: 211,
+                got: 20
+            })
+        );
+
+        // `createpsbt` with no inputs: the leading 0x00 is an input count,
+        // not a segwit marker
+        let no_inputs = BitcoinTransaction::new(
+            2,
+            vec![],
+            vec![TransactionOutput::new(1_000, dummy_p2wpkh(1))],
+            0,
+        );
+        assert_eq!(
+            BitcoinTransaction::from_psbt(&no_inputs.to_psbt()),
+            Ok(no_inputs)
+        );
+    }
+
+    #[test]
+    fn test_script_sigop_count() {
+        assert_eq!(dummy_p2pkh(1).sigop_count(false), 1);
+
+        // 2-of-3 multisig: 20 when inaccurate, 3 when accurate
+        let mut multisig = vec![0x52];
+        for _ in 0..3 {
+            multisig.push(0x21);
+            multisig.extend_from_slice(&[0x02; 33]);
+        }
+        multisig.extend_from_slice(&[0x53, 0xAE]);
+        let multisig = Script::new(multisig);
+        assert_eq!(multisig.sigop_count(false), 20);
+        assert_eq!(multisig.sigop_count(true), 3);
+
+        // CHECKSIG bytes inside push data are not counted
+        assert_eq!(Script::new(vec![0x02, 0xAC, 0xAC]).sigop_count(false), 0);
+    }
+
+    #[test]
+    fn test_sigop_cost_p2wpkh_spend() {
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![TransactionOutput::new(1_000, dummy_p2pkh(2))],
+            0,
+        )
+        .with_witnesses(vec![Witness::new(vec![vec![0x30; 71], vec![0x02; 33]])]);
+
+        // One legacy sigop in the P2PKH output (x4) plus one witness sigop
+        assert_eq!(tx.sigop_cost(&[dummy_p2wpkh(1)]), 5);
+        // Spending a P2PKH prevout has no witness sigops
+        assert_eq!(tx.sigop_cost(&[dummy_p2pkh(1)]), 4);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_mempool_entry_from_json() {
+        let json = serde_json::json!({
+            "vsize": 141,
+            "weight": 561,
+            "time": 1700000000,
+            "height": 820000,
+            "descendantcount": 1,
+            "descendantsize": 141,
+            "ancestorcount": 2,
+            "ancestorsize": 282,
+            "wtxid": "a1b2",
+            "fees": {
+                "base": 0.00002820,
+                "modified": 0.00002820,
+                "ancestor": 0.00003102,
+                "descendant": 0.00002820
+            },
+            "depends": [BLOCK_170_TXID],
+            "spentby": [],
+            "bip125-replaceable": true,
+            "unbroadcast": false
+        });
+
+        let entry = MempoolEntry::from_json(&json).unwrap();
+        assert_eq!(entry.vsize, 141);
+        assert_eq!(entry.fee, 2_820);
+        assert_eq!(entry.ancestor_count, 2);
+        assert_eq!(entry.depends, vec![block_170_tx().txid()]);
+
+        let legacy = serde_json::json!({
+            "vsize": 100,
+            "fee": 0.0001,
+            "ancestorcount": 1,
+            "depends": []
+        });
+        assert_eq!(MempoolEntry::from_json(&legacy).unwrap().fee, 10_000);
+
+        let missing = serde_json::json!({ "vsize": 100 });
+        assert_eq!(
+            MempoolEntry::from_json(&missing),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_serialized_entropy() {
+        let input = dummy_input(dummy_txid(1), 0);
+        let payment = BitcoinTransaction::new(
+            2,
+            vec![input.clone()],
+            vec![TransactionOutput::new(1_000, dummy_p2wpkh(1))],
+            0,
+        );
+
+        // OP_RETURN carrying 1000 pseudo-random bytes
+        let mut state = 0x2545F491u32;
+        let mut data_script = vec![0x6A, 0x4D, 0xE8, 0x03];
+        for _ in 0..1000 {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            data_script.push((state >> 16) as u8);
+        }
+        let data_heavy = BitcoinTransaction::new(
+            2,
+            vec![input],
+            vec![TransactionOutput::new(0, Script::new(data_script))],
+            0,
+        );
+
+        let low = payment.serialized_entropy();
+        let high = data_heavy.serialized_entropy();
+        assert!(low > 0.0 && low < 4.0);
+        assert!(high > 7.0 && high <= 8.0);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let dot = block_170_tx().to_dot();
+        assert!(dot.starts_with(&format!("\"tx_{}\"", BLOCK_170_TXID)));
+        assert!(
+            dot.contains(
+                "<in0> 0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9:0"
+            )
+        );
+        assert!(dot.contains("<out0> 0: 1000000000 sat"));
+        assert!(dot.contains("<out1> 1: 4000000000 sat"));
+        assert!(!dot.contains("<out2>"));
+    }
+
+    #[test]
+    fn test_p2pk_script() {
+        let mut compressed = vec![0x02];
+        compressed.extend_from_slice(&[0x11; 32]);
+        let script = Script::p2pk(&compressed);
+        assert_eq!(script.len(), 35);
+        assert_eq!(script[0], 0x21);
+        assert_eq!(script[34], 0xAC);
+        assert_eq!(script.classify(), ScriptType::P2PK);
+        assert_eq!(script.parse_p2pk(), Some(compressed));
+
+        // Block 170's second output pays Satoshi's uncompressed key
+        let tx = block_170_tx();
+        let uncompressed = tx.outputs[1].script_pubkey.parse_p2pk().unwrap();
+        assert_eq!(uncompressed.len(), 65);
+        assert_eq!(uncompressed[0], 0x04);
+        assert_eq!(Script::p2pk(&uncompressed), tx.outputs[1].script_pubkey);
+
+        assert_eq!(dummy_p2pkh(1).parse_p2pk(), None);
+    }
+
+    #[test]
+    fn test_change_matches_input_type() {
+        let p2wpkh_input = dummy_input(dummy_txid(1), 0);
+        let p2wpkh_witness = Witness::new(vec![vec![0x30; 71], vec![0x02; 33]]);
+
+        // Paying a P2PKH address with P2WPKH change
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![p2wpkh_input.clone()],
+            vec![
+                TransactionOutput::new(50_000, dummy_p2pkh(1)),
+                TransactionOutput::new(12_345, dummy_p2wpkh(2)),
+            ],
+            0,
+        )
+        .with_witnesses(vec![p2wpkh_witness.clone()]);
+        assert_eq!(tx.input_script_types(), vec![ScriptType::P2WPKH]);
+        assert!(tx.change_matches_input_type());
+
+        // Both outputs match the input type, so neither stands out
+        let ambiguous = BitcoinTransaction::new(
+            2,
+            vec![p2wpkh_input],
+            vec![
+                TransactionOutput::new(50_000, dummy_p2wpkh(1)),
+                TransactionOutput::new(12_345, dummy_p2wpkh(2)),
+            ],
+            0,
+        )
+        .with_witnesses(vec![p2wpkh_witness]);
+        assert!(!ambiguous.change_matches_input_type());
+
+        // Block 170 spends a P2PK output and pays two P2PK outputs
+        let block_170 = block_170_tx();
+        assert_eq!(block_170.input_script_types(), vec![ScriptType::P2PK]);
+        assert!(!block_170.change_matches_input_type());
+    }
+
+    #[test]
+    fn test_to_getdata_inv() {
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![TransactionOutput::new(1_000, dummy_p2wpkh(1))],
+            0,
+        )
+        .with_witnesses(vec![Witness::new(vec![vec![0x30; 71], vec![0x02; 33]])]);
+
+        let inv = tx.to_getdata_inv(false);
+        assert_eq!(inv.inv_type, MSG_TX);
+        assert_eq!(inv.hash, tx.txid().0);
+
+        let winv = tx.to_getdata_inv(true);
+        assert_eq!(winv.inv_type, MSG_WTX);
+        assert_eq!(winv.hash, tx.wtxid().0);
+        assert_ne!(winv.hash, inv.hash);
+
+        let bytes = winv.to_bytes();
+        assert_eq!(&bytes[..4], &[0x05, 0x00, 0x00, 0x00]);
+        assert_eq!(InvVector::from_bytes(&bytes).unwrap(), (winv, 36));
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let mut tx = spend(dummy_txid(1), 1);
+        tx.inputs.push(dummy_input(dummy_txid(2), 1));
+        let independent = spend(dummy_txid(2), 2);
+        let double_spend =
+            BitcoinTransaction::new(2, vec![dummy_input(dummy_txid(2), 1)], vec![], 0);
+
+        assert_eq!(
+            find_conflicts(&tx, &[independent, double_spend, tx.clone()]),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn test_base64_roundtrip() {
+        let tx = block_170_tx();
+        let encoded = tx.to_base64();
+        assert!(encoded.starts_with("AQAAAAHJl6Xl"));
+        assert_eq!(BitcoinTransaction::from_base64(&encoded).unwrap(), tx);
+
+        assert_eq!(
+            BitcoinTransaction::from_base64("not base64!"),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_coin_age_priority() {
+        let tx = block_170_tx();
+        // 50 BTC aged a day (144 blocks) over 275 bytes
+        let priority = tx.coin_age_priority(&[(144, 5_000_000_000)]);
+        assert!((priority - 144.0 * 5_000_000_000.0 / 275.0).abs() < 1e-3);
+        // Comfortably above the old "high priority" threshold of 57.6M
+        assert!(priority > 57_600_000.0);
+        assert_eq!(tx.coin_age_priority(&[]), 0.0);
+    }
+
+    #[test]
+    fn test_is_dust_thresholds() {
+        // The well-known 546 and 294 sat limits at the default dust feerate
+        assert!(TransactionOutput::new(545, dummy_p2pkh(1)).is_dust(DUST_RELAY_TX_FEE));
+        assert!(!TransactionOutput::new(546, dummy_p2pkh(1)).is_dust(DUST_RELAY_TX_FEE));
+        assert!(TransactionOutput::new(293, dummy_p2wpkh(1)).is_dust(DUST_RELAY_TX_FEE));
+        assert!(!TransactionOutput::new(294, dummy_p2wpkh(1)).is_dust(DUST_RELAY_TX_FEE));
+        assert!(!TransactionOutput::new(0, Script::new(vec![0x6A])).is_dust(DUST_RELAY_TX_FEE));
+    }
+
+    #[test]
+    fn test_partition_dust() {
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![
+                TransactionOutput::new(100, dummy_p2pkh(1)),
+                TransactionOutput::new(50_000, dummy_p2wpkh(2)),
+                TransactionOutput::new(200, dummy_p2wpkh(3)),
+                TransactionOutput::new(0, Script::new(vec![0x6A, 0x01, 0xFF])),
+            ],
+            0,
+        );
+        assert_eq!(
+            tx.partition_dust(DUST_RELAY_TX_FEE),
+            (vec![0, 2], vec![1, 3])
+        );
+        // An absurd feerate must not overflow: even the whole supply is dust
+        // at u64::MAX sat/kvB, while u64::MAX sats still clears the
+        // ~182-byte P2PKH threshold
+        assert!(TransactionOutput::new(MAX_MONEY, dummy_p2pkh(1)).is_dust(u64::MAX));
+        assert!(!TransactionOutput::new(u64::MAX, dummy_p2pkh(1)).is_dust(u64::MAX));
+        assert_eq!(tx.partition_dust(u64::MAX), (vec![0, 1, 2], vec![3]));
+    }
+
+    #[test]
+    fn test_weight_to_vsize() {
+        let expected = [1, 1, 1, 1, 2, 2, 2, 2];
+        for (weight, vsize) in (1..=8).zip(expected) {
+            assert_eq!(weight_to_vsize(weight), vsize);
+        }
+        assert_eq!(weight_to_vsize(0), 0);
+        assert_eq!(WITNESS_SCALE_FACTOR, 4);
+    }
+
+    #[test]
+    fn test_validate_output_scripts() {
+        let mut tx = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![
+                TransactionOutput::new(1_000, dummy_p2pkh(1)),
+                TransactionOutput::new(0, Script::new(vec![0x6A, 0x02, 0xDE, 0xAD])),
+            ],
+            0,
+        );
+        assert_eq!(tx.validate_output_scripts(), Ok(()));
+
+        // Push of 4 bytes with only 2 present
+        tx.outputs.push(TransactionOutput::new(
+            0,
+            Script::new(vec![0x6A, 0x04, 0xDE, 0xAD]),
+        ));
+        assert_eq!(
+            tx.validate_output_scripts(),
+            Err(BitcoinError::InvalidOutputScript { index: 2 })
+        );
+        assert_eq!(
+            Script::new(vec![0x4D, 0x01]).validate_pushes(),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let tx = block_170_tx();
+        assert_eq!(hex::encode(tx.fingerprint()), &BLOCK_170_TXID[..16]);
+
+        let bytes = hex::decode(BLOCK_170_TX_HEX).unwrap();
+        assert_eq!(compute_txid(&bytes), tx.txid());
+    }
+
+    #[test]
+    fn test_witness_annex_roundtrip() {
+        let annex = vec![0x50, 0x00, 0xFF, 0x13, 0x37, 0x00, 0x00];
+        let witness = Witness::new(vec![vec![0x01; 64], annex.clone()]);
+        assert_eq!(witness.annex(), Some(annex.as_slice()));
+        assert_eq!(Witness::new(vec![annex.clone()]).annex(), None);
+
+        let bytes = witness.to_bytes();
+        let (parsed, consumed) = Witness::from_bytes(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(parsed, witness);
+        assert_eq!(parsed.to_bytes(), bytes);
+
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![TransactionOutput::new(1_000, dummy_p2wpkh(1))],
+            0,
+        )
+        .with_witnesses(vec![witness]);
+        let tx_bytes = tx.to_bytes();
+        let (parsed_tx, _) = BitcoinTransaction::from_bytes(&tx_bytes).unwrap();
+        assert_eq!(parsed_tx.witnesses[0].annex(), Some(annex.as_slice()));
+        assert_eq!(parsed_tx.to_bytes(), tx_bytes);
+    }
+
+    #[test]
+    fn test_full_unlocking_data() {
+        // P2SH-P2WPKH: scriptSig pushes the witness program
+        let program = dummy_p2wpkh(7).bytes;
+        let mut script_sig = vec![program.len() as u8];
+        script_sig.extend_from_slice(&program);
+        let input = TransactionInput::new(
+            OutPoint::new(dummy_txid(1), 0),
+            Script::new(script_sig),
+            0xFFFFFFFF,
+        );
+        let witness = Witness::new(vec![vec![0x30; 71], vec![0x02; 33]]);
+
+        assert_eq!(
+            input.full_unlocking_data(Some(&witness)),
+            vec![program.clone(), vec![0x30; 71], vec![0x02; 33]]
+        );
+        assert_eq!(input.full_unlocking_data(None), vec![program]);
+    }
+
+    #[test]
+    fn test_execute_push_only() {
+        // OP_0 <2 bytes> OP_16 OP_1NEGATE OP_PUSHDATA1 <1 byte>
+        let script = Script::new(vec![0x00, 0x02, 0xAB, 0xCD, 0x60, 0x4F, 0x4C, 0x01, 0xEE]);
+        assert_eq!(
+            script.execute_push_only().unwrap(),
+            vec![vec![], vec![0xAB, 0xCD], vec![0x10], vec![0x81], vec![0xEE]]
+        );
+
+        // OP_DUP is not a push
+        assert_eq!(
+            Script::new(vec![0x01, 0xAB, 0x76]).execute_push_only(),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert_eq!(
+            block_170_tx().inputs[0]
+                .script_sig
+                .execute_push_only()
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_canonical_bytes_hash_to_txid() {
+        let tx = block_170_tx();
+        assert_eq!(hex::encode(tx.canonical_bytes()), BLOCK_170_TX_HEX);
+
+        let mut digest = hash256(&tx.canonical_bytes());
+        digest.reverse();
+        assert_eq!(hex::encode(digest), BLOCK_170_TXID);
+
+        // Witnesses never reach the txid preimage
+        let segwit = tx
+            .clone()
+            .with_witnesses(vec![Witness::new(vec![vec![0x01]])]);
+        assert_eq!(segwit.canonical_bytes(), tx.canonical_bytes());
+    }
+
+    #[test]
+    fn test_op_return_protocol() {
+        let op_return = |payload: &[u8]| {
+            let mut bytes = vec![0x6A, payload.len() as u8];
+            bytes.extend_from_slice(payload);
+            Script::new(bytes)
+        };
+
+        assert_eq!(
+            op_return(b"omni\x00\x00\x00\x00").op_return_protocol(),
+            Some("Omni")
+        );
+        assert_eq!(
+            op_return(b"OA\x01\x00\x01\x05").op_return_protocol(),
+            Some("OpenAssets")
+        );
+        assert_eq!(
+            op_return(b"DOCPROOF\xAA\xBB").op_return_protocol(),
+            Some("DocProof")
+        );
+        assert_eq!(op_return(b"hello world").op_return_protocol(), None);
+        assert_eq!(dummy_p2pkh(1).op_return_protocol(), None);
+    }
+
+    #[test]
+    fn test_value_flow() {
+        let tx = block_170_tx();
+        assert_eq!(
+            tx.value_flow(&[5_000_000_000]).unwrap(),
+            ValueFlow {
+                total_in: 5_000_000_000,
+                total_out: 5_000_000_000,
+                fee: 0,
+            }
+        );
+        assert_eq!(tx.value_flow(&[5_000_010_000]).unwrap().fee, 10_000);
+
+        // Wrong number of input values, or outputs exceeding inputs
+        assert_eq!(tx.value_flow(&[]), Err(BitcoinError::InvalidFormat));
+        assert_eq!(tx.value_flow(&[1]), Err(BitcoinError::InvalidFormat));
+    }
+
+    #[test]
+    fn test_builder_sequence_helpers() {
+        let tx = TransactionBuilder::new(2)
+            .add_final_input(OutPoint::new(dummy_txid(1), 0), Script::new(vec![]))
+            .add_rbf_input(OutPoint::new(dummy_txid(2), 1), Script::new(vec![]))
+            .add_output(10_000, dummy_p2wpkh(1))
+            .lock_time(800_000)
+            .build();
+
+        assert_eq!(tx.inputs[0].sequence.to_u32(), 0xFFFFFFFF);
+        assert_eq!(tx.inputs[1].sequence.to_u32(), 0xFFFFFFFD);
+        assert_eq!(
+            tx.inputs[1].previous_output,
+            OutPoint::new(dummy_txid(2), 1)
+        );
+        assert_eq!(
+            tx.outputs,
+            vec![TransactionOutput::new(10_000, dummy_p2wpkh(1))]
+        );
+        assert_eq!(tx.lock_time, LockTime::Blocks(800_000));
+    }
+
+    #[test]
+    fn test_sort_for_block() {
+        let parent = spend(dummy_txid(1), 1);
+        let child = spend(parent.txid().0, 2);
+        let middle = spend(dummy_txid(3), 3);
+
+        // The child pays the most, but must still follow its low-fee parent
+        let mut txs = vec![child.clone(), middle.clone(), parent.clone()];
+        sort_for_block(&mut txs, &[8_200, 4_100, 82]);
+        assert_eq!(txs, vec![middle, parent, child]);
+    }
+
+    #[test]
+    fn test_input_from_bytes_no_scriptsig() {
+        let input = TransactionInput::new(
+            OutPoint::new(dummy_txid(5), 2),
+            Script::new(vec![]),
+            0xFFFFFFFD,
+        );
+        let mut bytes = input.to_bytes();
+        bytes.extend_from_slice(&[0xAA; 8]);
+
+        // 36-byte outpoint + 1-byte empty script length + 4-byte sequence
+        let (parsed, consumed) = TransactionInput::from_bytes_no_scriptsig(&bytes).unwrap();
+        assert_eq!(parsed, input);
+        assert_eq!(consumed, 41);
+        assert_eq!(
+            TransactionInput::from_bytes(&bytes).unwrap(),
+            (parsed, consumed)
+        );
+
+        let with_script_sig = TransactionInput::new(
+            OutPoint::new(dummy_txid(5), 2),
+            Script::new(vec![0x51]),
+            0xFFFFFFFD,
+        );
+        assert_eq!(
+            TransactionInput::from_bytes_no_scriptsig(&with_script_sig.to_bytes()),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_max_signed_vsize() {
+        let outputs = vec![dummy_p2wpkh(1), dummy_p2wpkh(2)];
+        let estimate = max_signed_vsize(&[InputKind::P2WPKH, InputKind::P2WPKH], &outputs);
+        assert_eq!(estimate, 209);
+
+        // A signed transaction with worst-case signatures lands exactly on it
+        let signed = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0), dummy_input(dummy_txid(2), 0)],
+            outputs
+                .iter()
+                .map(|script| TransactionOutput::new(10_000, script.clone()))
+                .collect(),
+            0,
+        )
+        .with_witnesses(vec![
+            Witness::new(vec![vec![0x30; 73], vec![0x02; 33]]),
+            Witness::new(vec![vec![0x30; 73], vec![0x02; 33]]),
+        ]);
+        assert_eq!(signed.vsize(), estimate);
+
+        // Legacy-only spends carry no witness discount, and the bound covers
+        // a signature with an uncompressed pubkey
+        let mut script_sig = Script::new(vec![]);
+        script_sig.push_data(&[0x30; 73]);
+        script_sig.push_data(&[0x04; 65]);
+        let legacy = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                script_sig,
+                0xFFFFFFFF,
+            )],
+            vec![TransactionOutput::new(1_000, dummy_p2pkh(1))],
+            0,
+        );
+        assert_eq!(legacy.vsize(), 225);
+        assert_eq!(
+            max_signed_vsize(&[InputKind::P2PKH], &[dummy_p2pkh(1)]),
+            legacy.vsize()
+        );
+    }
+
+    #[test]
+    fn test_is_data_carrier() {
+        assert!(Script::new(vec![0x6A, 0x03, b'a', b'b', b'c']).is_data_carrier());
+        assert!(!Script::new(vec![0x6A]).is_data_carrier());
+
+        let multisig = |keys: &[[u8; 33]]| {
+            let mut bytes = vec![0x51];
+            for key in keys {
+                bytes.push(0x21);
+                bytes.extend_from_slice(key);
+            }
+            bytes.extend_from_slice(&[0x50 + keys.len() as u8, 0xAE]);
+            Script::new(bytes)
+        };
+        let mut real_key = [0x11; 33];
+        real_key[0] = 0x02;
+
+        // 1-of-3 with two "keys" that are really 33-byte chunks of a file
+        let stuffed = multisig(&[real_key, [b'D'; 33], [b'A'; 33]]);
+        assert_eq!(stuffed.classify(), ScriptType::Multisig);
+        assert!(stuffed.is_data_carrier());
+
+        assert!(!multisig(&[real_key, real_key]).is_data_carrier());
+        assert!(!dummy_p2pkh(1).is_data_carrier());
+    }
+
+    #[test]
+    fn test_block_header_leading_zero_bits() {
+        let mut merkle_root =
+            hex::decode("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b")
+                .unwrap();
+        merkle_root.reverse();
+        let genesis = BlockHeader::new(
+            1,
+            [0u8; 32],
+            merkle_root.try_into().unwrap(),
+            1_231_006_505,
+            0x1d00ffff,
+            2_083_236_893,
+        );
+
+        let mut hash = genesis.block_hash();
+        hash.reverse();
+        assert_eq!(
+            hex::encode(hash),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        // Five zero bytes, then 0x19 = 0b0001_1001
+        assert_eq!(genesis.leading_zero_bits(), 43);
+
+        let bytes = genesis.to_bytes();
+        assert_eq!(bytes.len(), 80);
+        assert_eq!(BlockHeader::from_bytes(&bytes).unwrap(), (genesis, 80));
+    }
+
+    #[test]
+    fn test_op_return_output() {
+        let output = TxOut::op_return(&[0xAB; 40]).unwrap();
+        assert_eq!(output.value, Amount::ZERO);
+        assert_eq!(&output.script_pubkey[..2], &[0x6A, 0x28]);
+        assert_eq!(output.script_pubkey.len(), 42);
+        assert_eq!(output.script_pubkey.classify(), ScriptType::OpReturn);
+
+        let max = TxOut::op_return(&[0xAB; 80]).unwrap();
+        assert_eq!(&max.script_pubkey[..3], &[0x6A, 0x4C, 0x50]);
+        assert_eq!(max.script_pubkey.validate_pushes(), Ok(()));
+
+        assert_eq!(
+            TxOut::op_return(&[0xAB; 81]),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_taint_paths() {
+        use std::collections::HashMap;
+
+        let a = spend(dummy_txid(1), 1);
+        let b = spend(a.txid().0, 2);
+        let c = spend(b.txid().0, 3);
+
+        let mut graph = HashMap::new();
+        for tx in [&a, &b, &c] {
+            graph.insert(tx.txid(), tx.clone());
+        }
+
+        assert_eq!(
+            taint_paths(a.txid(), &graph, 10),
+            vec![vec![a.txid(), b.txid(), c.txid()]]
+        );
+        assert_eq!(
+            taint_paths(a.txid(), &graph, 1),
+            vec![vec![a.txid(), b.txid()]]
+        );
+        assert!(taint_paths(c.txid(), &graph, 10).is_empty());
+    }
+
+    #[test]
+    fn test_validate_output_values() {
+        let with_values = |values: &[u64]| {
+            BitcoinTransaction::new(
+                2,
+                vec![dummy_input(dummy_txid(1), 0)],
+                values
+                    .iter()
+                    .map(|value| TransactionOutput::new(*value, dummy_p2wpkh(1)))
+                    .collect(),
+                0,
+            )
+        };
+
+        assert_eq!(with_values(&[MAX_MONEY]).validate_output_values(), Ok(()));
+        assert_eq!(
+            with_values(&[MAX_MONEY + 1]).validate_output_values(),
+            Err(BitcoinError::InvalidFormat)
+        );
+        // Each output is in range but the sum is not
+        assert_eq!(
+            with_values(&[MAX_MONEY, 1]).validate_output_values(),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert_eq!(
+            with_values(&[u64::MAX, u64::MAX]).validate_output_values(),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_bitcoin_error_display_and_error_trait() {
+        assert_eq!(
+            BitcoinError::InsufficientBytes {
+                needed: 36,
+                got: 10
+            }
+            .to_string(),
+            "insufficient bytes: needed 36, got 10"
+        );
+        assert_eq!(BitcoinError::InvalidFormat.to_string(), "invalid format");
+
+        fn parse(bytes: &[u8]) -> Result<BitcoinTransaction, Box<dyn std::error::Error>> {
+            Ok(BitcoinTransaction::from_bytes(bytes)?.0)
+        }
+        let err = parse(&[0x01, 0x00]).unwrap_err();
+        assert_eq!(err.to_string(), "insufficient bytes: needed 4, got 2");
+
+        // A sequence cut short deep inside an input reports the shortfall
+        let input = TransactionInput::new(
+            OutPoint::new(dummy_txid(1), 0),
+            Script::new(vec![0x01, 0x02]),
+            0xFFFFFFFF,
+        );
+        let bytes = input.to_bytes();
+        assert_eq!(
+            TransactionInput::from_bytes(&bytes[..bytes.len() - 3]),
+            Err(BitcoinError::InsufficientBytes {
+                needed: bytes.len(),
+                got: bytes.len() - 3,
+            })
+        );
+    }
+
+    #[test]
+    fn test_segwit_fee_savings() {
+        let legacy = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![TransactionOutput::new(10_000, dummy_p2wpkh(1))],
+            0,
+        );
+        assert_eq!(legacy.segwit_fee_savings(10), 0);
+
+        // 82 base bytes + 110 witness bytes: 192 bytes but only 110 vB
+        let p2wpkh =
+            legacy.with_witnesses(vec![Witness::new(vec![vec![0x30; 72], vec![0x02; 33]])]);
+        assert_eq!(p2wpkh.to_bytes().len(), 192);
+        assert_eq!(p2wpkh.vsize(), 110);
+        assert_eq!(p2wpkh.segwit_fee_savings(10), 820);
+        assert_eq!(p2wpkh.segwit_fee_savings(u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn test_txid_display_order() {
+        use std::str::FromStr;
+
+        let txid = Txid::from_str(BLOCK_170_TXID).unwrap();
+        assert_eq!(txid, block_170_tx().txid());
+        assert_eq!(txid.to_string(), BLOCK_170_TXID);
+
+        // Raw bytes are the reverse of the display form
+        let raw = txid.to_raw_bytes();
+        assert_eq!(raw[31], 0xF4);
+        assert_eq!(raw[0], 0x16);
+        assert_eq!(Txid::from_raw_bytes(raw), txid);
+
+        assert_eq!(Txid::from_str("f4184f"), Err(BitcoinError::InvalidFormat));
+        assert_eq!(
+            Txid::from_str(&"zz".repeat(32)),
+            Err(BitcoinError::InvalidFormat)
+        );
+
+        // The transaction Display uses the explorer form too
+        let spend = BitcoinTransaction::new(1, vec![dummy_input(raw, 0)], vec![], 0);
+        assert!(
+            format!("{}", spend).contains(&format!("Previous Output Txid: {}", BLOCK_170_TXID))
+        );
+    }
+
+    #[test]
+    fn test_parse_hex_lines() {
+        let segwit = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![TransactionOutput::new(1_000, dummy_p2wpkh(1))],
+            0,
+        )
+        .with_witnesses(vec![Witness::new(vec![vec![0x01; 64]])]);
+
+        let input = format!(
+            "{}\n\n   \nnot hex\n  {}  \n{}00\n0100",
+            BLOCK_170_TX_HEX,
+            hex::encode(segwit.to_bytes()),
+            BLOCK_170_TX_HEX
+        );
+        let results = parse_hex_lines(&input);
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0], Ok(block_170_tx()));
+        assert_eq!(results[1], Err(BitcoinError::InvalidFormat));
+        assert_eq!(results[2], Ok(segwit));
+        // Trailing garbage and truncated transactions are per-line errors
+        assert_eq!(results[3], Err(BitcoinError::InvalidFormat));
+        assert!(matches!(
+            results[4],
+            Err(BitcoinError::InsufficientBytes { .. })
+        ));
+    }
+
+    #[test]
+    fn test_vsize_with_witness() {
+        let unsigned = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![TransactionOutput::new(1_000, dummy_p2wpkh(1))],
+            0,
+        );
+        let witness = Witness::new(vec![vec![0x30; 72], vec![0x02; 33]]);
+
+        assert_eq!(unsigned.vsize(), 82);
+        assert_eq!(unsigned.vsize_with_witness(&[]), 82);
+        let predicted = unsigned.vsize_with_witness(std::slice::from_ref(&witness));
+        assert_eq!(predicted, 110);
+
+        let signed = unsigned.clone().with_witnesses(vec![witness]);
+        assert_eq!(signed.vsize(), predicted);
+        assert!(unsigned.witnesses.is_empty());
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_huge_input_count() {
+        // Version followed by a CompactSize claiming u64::MAX inputs
+        let mut bytes = vec![0x01, 0x00, 0x00, 0x00, 0xFF];
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            BitcoinTransaction::from_bytes(&bytes),
+            Err(BitcoinError::InsufficientBytes { got: 0, .. })
+        ));
+
+        // 3 inputs claimed, but only 41 bytes follow
+        let mut bytes = vec![0x01, 0x00, 0x00, 0x00, 0x03];
+        bytes.extend_from_slice(&[0u8; 41]);
+        assert_eq!(
+            BitcoinTransaction::from_bytes(&bytes),
+            Err(BitcoinError::InsufficientBytes {
+                needed: 123,
+                got: 41
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_bytes_with_limit() {
+        let bytes = block_170_tx().to_bytes();
+        assert!(BitcoinTransaction::from_bytes_with_limit(&bytes, 1).is_ok());
+        assert_eq!(
+            BitcoinTransaction::from_bytes_with_limit(&bytes, 0),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_to_table() {
+        let table = block_170_tx().to_table();
+        let lines: Vec<&str> = table.lines().collect();
+
+        // Header + 1 input, blank separator, header + 2 outputs
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], format!("INPUT  {:<66}  SEQUENCE", "OUTPOINT"));
+        assert_eq!(
+            lines[1],
+            "0      0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9:0  0xffffffff"
+        );
+        assert_eq!(lines[2], "");
+        assert_eq!(lines[3], "OUTPUT  VALUE       TYPE");
+        assert_eq!(lines[4], "0       1000000000  P2PK");
+        assert_eq!(lines[5], "1       4000000000  P2PK");
+    }
+
+    #[test]
+    fn test_txid_genesis_coinbase() {
+        let bytes = hex::decode(
+            "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000",
+        )
+        .unwrap();
+        let expected = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+
+        assert_eq!(compute_txid(&bytes).to_string(), expected);
+        let (tx, _) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert!(tx.is_coinbase());
+        assert_eq!(tx.txid().to_string(), expected);
+    }
+
+    #[test]
+    fn test_is_sweep() {
+        let input = dummy_input(dummy_txid(1), 0);
+        let sweep = BitcoinTransaction::new(
+            2,
+            vec![input.clone()],
+            vec![TransactionOutput::new(99_000, dummy_p2wpkh(1))],
+            0,
+        );
+        assert!(sweep.is_sweep());
+
+        let payment = BitcoinTransaction::new(
+            2,
+            vec![input],
+            vec![
+                TransactionOutput::new(50_000, dummy_p2wpkh(1)),
+                TransactionOutput::new(49_000, dummy_p2wpkh(2)),
+            ],
+            0,
+        );
+        assert!(!payment.is_sweep());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_consensus_encode_decode_streaming() {
+        use sha2::{Digest, Sha256};
+        use std::io::Cursor;
+
+        let legacy = block_170_tx();
+        let segwit = legacy
+            .clone()
+            .with_witnesses(vec![Witness::new(vec![vec![0x30; 71], vec![0x02; 33]])]);
+
+        for tx in [&legacy, &segwit] {
+            let mut stream = Vec::new();
+            let written = tx.consensus_encode(&mut stream).unwrap();
+            assert_eq!(written, stream.len());
+            assert_eq!(stream, tx.to_bytes());
+
+            // Two transactions back to back: each decode stops at its end
+            stream.extend_from_slice(&tx.to_bytes());
+            let mut reader = Cursor::new(stream);
+            assert_eq!(
+                BitcoinTransaction::consensus_decode(&mut reader).unwrap(),
+                *tx
+            );
+            assert_eq!(
+                BitcoinTransaction::consensus_decode(&mut reader).unwrap(),
+                *tx
+            );
+            assert_eq!(reader.position() as usize, 2 * written);
+        }
+
+        // Straight into a hasher, no intermediate buffer
+        let mut hasher = Sha256::new();
+        legacy.consensus_encode(&mut hasher).unwrap();
+        let mut digest: [u8; 32] = Sha256::digest(hasher.finalize()).into();
+        digest.reverse();
+        assert_eq!(hex::encode(digest), BLOCK_170_TXID);
+
+        // Truncated streams and non-canonical counts are rejected
+        let bytes = legacy.to_bytes();
+        let mut truncated = Cursor::new(&bytes[..bytes.len() - 1]);
+        assert!(matches!(
+            BitcoinTransaction::consensus_decode(&mut truncated),
+            Err(BitcoinError::InsufficientBytes { .. })
+        ));
+        assert_eq!(
+            CompactSize::consensus_decode(&mut Cursor::new([0xFD, 0x10, 0x00])),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_consensus_decode_huge_script_length() {
+        // A scriptSig claiming u64::MAX bytes must fail on the short stream
+        // instead of trying to allocate them
+        let mut bytes = vec![0xFF];
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        bytes.extend_from_slice(&[0xAB; 3]);
+        assert!(matches!(
+            Script::consensus_decode(&mut &bytes[..]),
+            Err(BitcoinError::InsufficientBytes { got: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_bytes_needed() {
+        let bytes = block_170_tx().to_bytes();
+        assert_eq!(BitcoinTransaction::bytes_needed(&bytes), Ok(0));
+
+        // Missing the whole lock_time, then just its last byte
+        assert_eq!(
+            BitcoinTransaction::bytes_needed(&bytes[..bytes.len() - 4]),
+            Ok(4)
+        );
+        assert_eq!(
+            BitcoinTransaction::bytes_needed(&bytes[..bytes.len() - 1]),
+            Ok(1)
+        );
+        assert_eq!(BitcoinTransaction::bytes_needed(&bytes[..2]), Ok(2));
+
+        let mut bad_flag = bytes.clone();
+        bad_flag.splice(4..4, [0x00, 0x02]);
+        assert_eq!(
+            BitcoinTransaction::bytes_needed(&bad_flag),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_segwit_known_vector() {
+        let bytes = hex::decode(BIP143_P2WPKH_TX_HEX).unwrap();
+        let (tx, consumed) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+
+        assert_eq!(tx.version, 1);
+        assert_eq!(tx.inputs.len(), 2);
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.lock_time.to_u32(), 0x11);
+        assert_eq!(tx.inputs[0].sequence.to_u32(), 0xFFFFFFEE);
+        assert_eq!(tx.inputs[0].script_sig.len(), 0x49);
+        assert!(tx.inputs[1].script_sig.is_empty());
+
+        // The legacy input carries an empty witness, the segwit one sig + pubkey
+        assert_eq!(tx.witnesses.len(), 2);
+        assert!(tx.witnesses[0].is_empty());
+        let (signature, pubkey) = tx.witnesses[1].p2wpkh_parts().unwrap();
+        assert_eq!(signature.len(), 0x47);
+        assert_eq!(pubkey[0], 0x02);
+
+        assert_eq!(tx.to_bytes(), bytes);
+        assert_eq!(tx.weight(), 4 * tx.to_bytes_legacy().len() + 2 + 1 + 107);
+    }
+
+    #[test]
+    fn test_deterministic_sample() {
+        let a = BitcoinTransaction::deterministic_sample(42);
+        let b = BitcoinTransaction::deterministic_sample(42);
+        assert_eq!(a.to_bytes(), b.to_bytes());
+        assert_ne!(
+            a.to_bytes(),
+            BitcoinTransaction::deterministic_sample(43).to_bytes()
+        );
+
+        for seed in 0..20 {
+            let tx = BitcoinTransaction::deterministic_sample(seed);
+            let bytes = tx.to_bytes();
+            assert_eq!(
+                BitcoinTransaction::from_bytes(&bytes),
+                Ok((tx.clone(), bytes.len()))
+            );
+            assert!(
+                tx.outputs
+                    .iter()
+                    .all(|output| output.script_pubkey.classify() == ScriptType::P2WPKH)
+            );
+        }
+    }
+
+    #[test]
+    fn test_wtxid_differs_from_txid_for_segwit() {
+        let bytes = hex::decode(BIP143_P2WPKH_TX_HEX).unwrap();
+        let (tx, _) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            tx.txid().to_string(),
+            "e8151a2af31c368a35053ddd4bdb285a8595c769a3ad83e0fa02314a602d4609"
+        );
+        assert_eq!(
+            tx.wtxid().to_string(),
+            "d90d89331a323563108f040f7ed8766062171f82148673939c76a3dd9763fc47"
+        );
+
+        // Without witnesses the two hashes coincide
+        let legacy = block_170_tx();
+        assert_eq!(legacy.wtxid(), legacy.txid());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_prevout_fetch_plan() {
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![
+                dummy_input(dummy_txid(1), 3),
+                dummy_input(dummy_txid(2), 0),
+                dummy_input(dummy_txid(1), 0),
+            ],
+            vec![TransactionOutput::new(1_000, dummy_p2wpkh(1))],
+            0,
+        );
+
+        let plan = tx.prevout_fetch_plan();
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[&Txid(dummy_txid(1))], vec![3, 0]);
+        assert_eq!(plan[&Txid(dummy_txid(2))], vec![0]);
+
+        let coinbase = BitcoinTransaction::new(
+            1,
+            vec![dummy_input([0u8; 32], 0xFFFFFFFF)],
+            vec![TransactionOutput::new(50_000, dummy_p2wpkh(1))],
+            0,
+        );
+        assert!(coinbase.prevout_fetch_plan().is_empty());
+    }
+
+    #[test]
+    fn test_compact_size_conversions_and_ordering() {
+        let small = CompactSize::from(252);
+        let large: CompactSize = 0x1_0000_0000u64.into();
+        assert_eq!(small, CompactSize::new(252));
+        assert_eq!(u64::from(large.clone()), 0x1_0000_0000);
+        assert!(small < large);
+        assert_eq!([large.clone(), small.clone()].iter().max(), Some(&large));
+
+        assert_eq!(usize::try_from(small.clone()), Ok(252));
+        assert_eq!(small.encoded_len(), small.to_bytes().len());
+        assert_eq!(large.encoded_len(), 9);
+    }
+
+    #[test]
+    fn test_has_canonical_scriptsig() {
+        let input = |script: Vec<u8>| {
+            TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(script),
+                0xFFFFFFFF,
+            )
+        };
+
+        // The block 170 scriptSig is a single direct signature push
+        assert!(block_170_tx().inputs[0].has_canonical_scriptsig());
+        // OP_0 <sig> OP_1
+        let mut multisig = vec![0x00, 0x03, 0xAA, 0xBB, 0xCC, 0x51];
+        assert!(input(multisig.clone()).has_canonical_scriptsig());
+        assert!(input(vec![]).has_canonical_scriptsig());
+
+        // OP_DUP is not a push
+        multisig.push(0x76);
+        assert!(!input(multisig).has_canonical_scriptsig());
+        // OP_PUSHDATA1 for 3 bytes, a direct push of 0x05, an empty direct
+        // push spelled with PUSHDATA1
+        assert!(!input(vec![0x4C, 0x03, 0xAA, 0xBB, 0xCC]).has_canonical_scriptsig());
+        assert!(!input(vec![0x01, 0x05]).has_canonical_scriptsig());
+        assert!(!input(vec![0x4C, 0x00]).has_canonical_scriptsig());
+        // Truncated push
+        assert!(!input(vec![0x05, 0xAA]).has_canonical_scriptsig());
+
+        let mut long_push = vec![0x4C, 0x50];
+        long_push.extend_from_slice(&[0xAB; 0x50]);
+        assert!(Script::new(long_push).has_minimal_pushes());
+    }
+
+    #[test]
+    fn test_weight_and_vsize_known_vectors() {
+        // Block 170's spend: 275 bytes, weight 1100 as shown by explorers
+        let legacy = block_170_tx();
+        assert_eq!(legacy.weight(), 4 * 275);
+        assert_eq!(legacy.vsize(), 275);
+
+        // BIP143 P2WPKH: 110 of its 343 bytes are marker, flag and witnesses
+        let bytes = hex::decode(BIP143_P2WPKH_TX_HEX).unwrap();
+        let (segwit, _) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(bytes.len(), 343);
+        assert_eq!(segwit.to_bytes_legacy().len(), 233);
+        assert_eq!(segwit.weight(), 233 * 3 + 343);
+        assert_eq!(segwit.vsize(), 261);
+    }
+
+    #[test]
+    fn test_block_output_scripts() {
+        let first = block_170_tx();
+        let second = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![
+                TransactionOutput::new(1_000, dummy_p2wpkh(1)),
+                TransactionOutput::new(2_000, dummy_p2pkh(2)),
+                TransactionOutput::new(3_000, dummy_p2wpkh(1)),
+            ],
+            0,
+        );
+
+        let scripts = block_output_scripts(&[first.clone(), second]);
+        assert_eq!(scripts.len(), first.outputs.len() + 3);
+        assert_eq!(scripts[0], first.outputs[0].script_pubkey);
+        assert_eq!(scripts[3], dummy_p2pkh(2));
+        assert_eq!(scripts[2], scripts[4]);
+        assert!(block_output_scripts(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let hash = "ab".repeat(20);
+        assert_eq!(
+            dummy_p2pkh(0xAB).disassemble(),
+            Ok(format!(
+                "OP_DUP OP_HASH160 {} OP_EQUALVERIFY OP_CHECKSIG",
+                hash
+            ))
+        );
+        assert_eq!(
+            dummy_p2wpkh(0xAB).disassemble(),
+            Ok(format!("OP_0 {}", hash))
+        );
+        assert_eq!(
+            Script::new(vec![0x52, 0x4C, 0x02, 0xCA, 0xFE, 0x51, 0xAE, 0xBB, 0xFF]).disassemble(),
+            Ok(
+                "OP_2 OP_PUSHDATA1 cafe OP_1 OP_CHECKMULTISIG OP_UNKNOWN(0xbb) OP_UNKNOWN(0xff)"
+                    .to_string()
+            )
+        );
+        assert_eq!(Script::new(vec![]).disassemble(), Ok(String::new()));
+
+        // Push of 5 bytes with only 2 present
+        assert_eq!(
+            Script::new(vec![0x6A, 0x05, 0xDE, 0xAD]).disassemble(),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_display_alternate_shows_asm() {
+        let tx = block_170_tx();
+        let plain = format!("{}", tx);
+        let asm = format!("{:#}", tx);
+
+        let script_sig = hex::encode(&tx.inputs[0].script_sig.bytes);
+        assert!(plain.contains(&format!("Script Sig: {}", script_sig)));
+        assert!(!plain.contains("Script Pubkey:"));
+
+        // The scriptSig is a single signature push
+        assert!(asm.contains(&format!("Script Sig: {}", &script_sig[2..])));
+        assert!(asm.contains("Script Pubkey: 04ae1a62fe"));
+        assert!(asm.contains(" OP_CHECKSIG\n"));
+    }
+
+    #[test]
+    fn test_script_builders() {
+        let hash = [0x11; 20];
+
+        let mut expected = vec![0x76, 0xA9, 0x14];
+        expected.extend_from_slice(&hash);
+        expected.extend_from_slice(&[0x88, 0xAC]);
+        assert_eq!(Script::p2pkh(hash).bytes, expected);
+        assert_eq!(Script::p2pkh(hash), dummy_p2pkh(0x11));
+        assert_eq!(Script::p2pkh(hash).classify(), ScriptType::P2PKH);
+
+        let mut expected = vec![0x00, 0x14];
+        expected.extend_from_slice(&hash);
+        assert_eq!(Script::p2wpkh(hash).bytes, expected);
+        assert_eq!(Script::p2wpkh(hash).classify(), ScriptType::P2WPKH);
+    }
+
+    #[test]
+    fn test_push_data_opcode_selection() {
+        let pushed = |len: usize| {
+            let mut script = Script::new(vec![]);
+            script.push_data(&vec![0xAB; len]);
+            script
+        };
+
+        assert_eq!(pushed(0).bytes, vec![0x00]);
+        assert_eq!(pushed(1).bytes, vec![0x01, 0xAB]);
+        assert_eq!(&pushed(75).bytes[..1], &[0x4B]);
+        assert_eq!(&pushed(76).bytes[..2], &[0x4C, 0x4C]);
+        assert_eq!(&pushed(255).bytes[..2], &[0x4C, 0xFF]);
+        assert_eq!(&pushed(256).bytes[..3], &[0x4D, 0x00, 0x01]);
+        assert_eq!(&pushed(0x10000).bytes[..5], &[0x4E, 0x00, 0x00, 0x01, 0x00]);
+        for (len, prefix_len) in [(0, 1), (75, 1), (76, 2), (256, 3), (0x10000, 5)] {
+            let script = pushed(len);
+            assert_eq!(script.bytes.len(), prefix_len + len);
+            assert_eq!(script.last_push(), Some(vec![0xAB; len]));
+        }
+    }
+
+    #[test]
+    fn test_to_stable_json() {
+        let tx = BitcoinTransaction::deterministic_sample(7);
+        let json = tx.to_stable_json();
+        assert_eq!(json, tx.to_stable_json());
+        assert_eq!(json, tx.clone().to_stable_json());
+
+        // Keys are sorted regardless of struct field order
+        assert!(json.starts_with("{\"inputs\":[{\"previous_output\":{\"txid\":"));
+        let lock_time = json.find("\"lock_time\"").unwrap();
+        let outputs = json.find("\"outputs\"").unwrap();
+        let version = json.find("\"version\"").unwrap();
+        let witnesses = json.find("\"witnesses\"").unwrap();
+        assert!(lock_time < outputs && outputs < version && version < witnesses);
+
+        // And it still round-trips through serde
+        let parsed: BitcoinTransaction = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, tx);
+    }
+
+    #[test]
+    fn test_from_bytes_with_stats() {
+        let bytes = hex::decode(BLOCK_170_TX_HEX).unwrap();
+        let (tx, stats) = BitcoinTransaction::from_bytes_with_stats(&bytes).unwrap();
+        assert_eq!(tx, block_170_tx());
+        assert_eq!(
+            stats,
+            ParseStats {
+                // 72-byte signature push vs. two 67-byte P2PK scripts
+                max_script_len: 72,
+                total_input_bytes: 36 + 1 + 72 + 4,
+                total_output_bytes: 2 * (8 + 1 + 67),
+            }
+        );
+        // Version, two counts and lock time make up the rest
+        assert_eq!(
+            stats.total_input_bytes + stats.total_output_bytes + 10,
+            bytes.len()
+        );
+
+        let mut trailing = bytes.clone();
+        trailing.push(0x00);
+        assert_eq!(
+            BitcoinTransaction::from_bytes_with_stats(&trailing),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_outpoint_null_and_coinbase() {
+        let null = OutPoint::null();
+        assert!(null.is_null());
+        assert_eq!(
+            hex::encode(null.to_bytes()),
+            format!("{}ffffffff", "00".repeat(32))
+        );
+        // Both halves must match
+        assert!(!OutPoint::new([0u8; 32], 0).is_null());
+        assert!(!OutPoint::new(dummy_txid(1), 0xFFFFFFFF).is_null());
+
+        // The genesis coinbase spends the null outpoint; block 170's spend does not
+        let genesis = hex::decode(
+            "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000",
+        )
+        .unwrap();
+        let (coinbase, _) = BitcoinTransaction::from_bytes(&genesis).unwrap();
+        assert!(coinbase.is_coinbase());
+        assert!(coinbase.parent_txids().is_empty());
+        assert!(!block_170_tx().is_coinbase());
+
+        // A null outpoint among several inputs is not a coinbase
+        let mut two_inputs = coinbase.clone();
+        two_inputs.inputs.push(block_170_tx().inputs[0].clone());
+        assert!(!two_inputs.is_coinbase());
+    }
+
+    #[test]
+    fn test_tx_byte_diff() {
+        let original = block_170_tx();
+        assert!(tx_byte_diff(&original, &original).is_empty());
+
+        // Sequence sits right after the 72-byte scriptSig: 4 + 1 + 36 + 1 + 72
+        let mut bumped = original.clone();
+        bumped.inputs[0].sequence = Sequence::from_u32(0xFFFFFFFD);
+        assert_eq!(tx_byte_diff(&original, &bumped), vec![(114, 0xFF, 0xFD)]);
+
+        // Dropping the last output shortens the serialization
+        let mut shorter = original.clone();
+        shorter.outputs.pop();
+        let diff = tx_byte_diff(&original, &shorter);
+        let (long, len) = (original.to_bytes(), shorter.to_bytes().len());
+        let tail: Vec<(usize, u8, u8)> = (len..long.len())
+            .map(|offset| (offset, long[offset], 0x00))
+            .collect();
+        assert!(diff.ends_with(&tail));
+        // The zero lock_time closes the tail even though it "matches" 0x00
+        assert_eq!(
+            diff[diff.len() - 4..],
+            [
+                (long.len() - 4, 0, 0),
+                (long.len() - 3, 0, 0),
+                (long.len() - 2, 0, 0),
+                (long.len() - 1, 0, 0)
+            ]
+        );
+        assert!(
+            diff[..diff.len() - tail.len()]
+                .iter()
+                .all(|(offset, _, _)| *offset < len)
+        );
+    }
+
+    #[test]
+    fn test_oversized_lengths_error_cleanly() {
+        // 0xFF-prefixed u64::MAX length with a couple of bytes behind it
+        let mut script = vec![0xFF];
+        script.extend_from_slice(&u64::MAX.to_le_bytes());
+        script.extend_from_slice(&[0xAB, 0xCD]);
+
+        let mut witness = vec![0x01];
+        witness.extend_from_slice(&script);
+
+        for (result, len) in [
+            (Script::from_bytes(&script).map(|_| ()), script.len()),
+            (Witness::from_bytes(&witness).map(|_| ()), witness.len()),
+        ] {
+            // Doesn't fit usize on 32-bit targets; on 64-bit it fits but
+            // can't be satisfied. Neither case may wrap or panic.
+            if cfg!(target_pointer_width = "64") {
+                assert_eq!(
+                    result,
+                    Err(BitcoinError::InsufficientBytes {
+                        needed: usize::MAX,
+                        got: len,
+                    })
+                );
+            } else {
+                assert_eq!(result, Err(BitcoinError::InvalidFormat));
+            }
+        }
+
+        // Input count that overflows usize * 41 is still a clean error
+        let mut tx = vec![0x01, 0x00, 0x00, 0x00, 0xFF];
+        tx.extend_from_slice(&(u64::MAX / 2).to_le_bytes());
+        assert!(BitcoinTransaction::from_bytes(&tx).is_err());
+    }
+
+    #[test]
+    fn test_fits_in_block() {
+        let with_script_len = |len: usize| {
+            BitcoinTransaction::new(
+                2,
+                vec![dummy_input(dummy_txid(1), 0)],
+                vec![TransactionOutput::new(0, Script::new(vec![0x6A; len]))],
+                0,
+            )
+        };
+
+        // 64 bytes of framing around the script, all non-witness
+        let at_limit = with_script_len(999_936);
+        assert_eq!(at_limit.weight(), MAX_BLOCK_WEIGHT);
+        assert!(at_limit.fits_in_block());
+
+        let over_limit = with_script_len(999_937);
+        assert_eq!(over_limit.weight(), MAX_BLOCK_WEIGHT + 4);
+        assert!(!over_limit.fits_in_block());
+        assert!(block_170_tx().fits_in_block());
+    }
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let tx = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert_eq!(tx, block_170_tx());
+        assert_eq!(tx.to_hex(), BLOCK_170_TX_HEX);
+
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert_eq!(segwit.to_hex(), BIP143_P2WPKH_TX_HEX);
+
+        // Bad hex, trailing garbage and truncation
+        assert_eq!(
+            BitcoinTransaction::from_hex("zz"),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert_eq!(
+            BitcoinTransaction::from_hex(&format!("{}00", BLOCK_170_TX_HEX)),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert!(matches!(
+            BitcoinTransaction::from_hex(&BLOCK_170_TX_HEX[..BLOCK_170_TX_HEX.len() - 2]),
+            Err(BitcoinError::InsufficientBytes { .. })
+        ));
+    }
+
+    #[test]
+    fn test_p2sh_redeem_script() {
+        // OP_2 <pk1> <pk2> <pk3> OP_3 OP_CHECKMULTISIG
+        let mut redeem_script = Script::new(vec![0x52]);
+        for key in 1..=3u8 {
+            let mut pubkey = [key; 33];
+            pubkey[0] = 0x02;
+            redeem_script.push_data(&pubkey);
+        }
+        redeem_script.bytes.extend_from_slice(&[0x53, 0xAE]);
+        assert_eq!(redeem_script.classify(), ScriptType::Multisig);
+
+        // OP_0 <sig1> <sig2> <redeem script>
+        let mut script_sig = Script::new(vec![0x00]);
+        script_sig.push_data(&[0x30; 71]);
+        script_sig.push_data(&[0x30; 72]);
+        script_sig.push_data(&redeem_script.bytes);
+        let input = TransactionInput::new(OutPoint::new(dummy_txid(1), 0), script_sig, 0xFFFFFFFF);
+        assert_eq!(input.p2sh_redeem_script(), Some(redeem_script));
+
+        // Not push-only, or nothing to extract
+        let mut not_push_only = input.clone();
+        not_push_only.script_sig.bytes.push(0x87);
+        assert_eq!(not_push_only.p2sh_redeem_script(), None);
+        let mut empty = input;
+        empty.script_sig = Script::new(vec![]);
+        assert_eq!(empty.p2sh_redeem_script(), None);
+    }
+
+    #[test]
+    fn test_deserialize_is_strict() {
+        let bytes = hex::decode(BLOCK_170_TX_HEX).unwrap();
+        assert_eq!(BitcoinTransaction::deserialize(&bytes), Ok(block_170_tx()));
+
+        let mut trailing = bytes.clone();
+        trailing.extend_from_slice(&[0xDE, 0xAD]);
+        assert_eq!(
+            BitcoinTransaction::deserialize(&trailing),
+            Err(BitcoinError::InvalidFormat)
+        );
+        // from_bytes still accepts it and reports where the tx ended
+        assert_eq!(
+            BitcoinTransaction::from_bytes(&trailing),
+            Ok((block_170_tx(), bytes.len()))
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_scan_block_for_scripts() {
+        use std::collections::HashSet;
+
+        let first = block_170_tx();
+        let second = BitcoinTransaction::new(
+            2,
+            vec![dummy_input(dummy_txid(1), 0)],
+            vec![
+                TransactionOutput::new(1_000, dummy_p2wpkh(1)),
+                TransactionOutput::new(2_000, dummy_p2pkh(2)),
+                TransactionOutput::new(3_000, dummy_p2wpkh(1)),
+            ],
+            0,
+        );
+
+        let created = second.created_outputs();
+        assert_eq!(created.len(), 3);
+        assert_eq!(created[2].0, OutPoint::new(second.txid().0, 2));
+        assert_eq!(created[2].1.value.to_sat(), 3_000);
+
+        // One of block 170's P2PK outputs plus both P2WPKH outputs
+        let watch: HashSet<Vec<u8>> = [
+            first.outputs[1].script_pubkey.bytes.clone(),
+            dummy_p2wpkh(1).bytes,
+        ]
+        .into_iter()
+        .collect();
+        let (received, utxos) = scan_block_for_scripts(&[first.clone(), second.clone()], &watch);
+        assert_eq!(received, 4_000_000_000 + 1_000 + 3_000);
+        assert_eq!(
+            utxos,
+            vec![
+                OutPoint::new(first.txid().0, 1),
+                OutPoint::new(second.txid().0, 0),
+                OutPoint::new(second.txid().0, 2),
+            ]
+        );
+
+        assert_eq!(
+            scan_block_for_scripts(&[first], &HashSet::new()),
+            (0, vec![])
+        );
+
+        // Oversized values on the wire saturate instead of overflowing
+        let mut oversized = second.clone();
+        for output in &mut oversized.outputs {
+            output.value = Amount::from_sat(u64::MAX);
+        }
+        let (received, utxos) = scan_block_for_scripts(&[oversized], &watch);
+        assert_eq!(received, u64::MAX);
+        assert_eq!(utxos.len(), 2);
+    }
+
+    #[test]
+    fn test_bitcoin_error_serde_format() {
+        let cases = [
+            (
+                BitcoinError::InsufficientBytes {
+                    needed: 36,
+                    got: 10,
+                },
+                r#"{"kind":"insufficient_bytes","needed":36,"got":10}"#,
+            ),
+            (BitcoinError::InvalidFormat, r#"{"kind":"invalid_format"}"#),
+            (
+                BitcoinError::InvalidOutputScript { index: 2 },
+                r#"{"kind":"invalid_output_script","index":2}"#,
+            ),
+        ];
+
+        for (error, json) in cases {
+            assert_eq!(serde_json::to_string(&error).unwrap(), json);
+            assert_eq!(serde_json::from_str::<BitcoinError>(json).unwrap(), error);
+        }
+        assert!(serde_json::from_str::<BitcoinError>(r#"{"kind":"unknown"}"#).is_err());
+    }
+
+    #[test]
+    fn test_rbf_descendant_ok() {
+        let original = spend(dummy_txid(1), 1);
+        let replacement = spend(dummy_txid(1), 2);
+        let child = spend(original.txid().0, 3);
+        let unrelated = spend(dummy_txid(2), 4);
+        assert!(rbf_descendant_ok(
+            &replacement,
+            &[original.clone(), child, unrelated]
+        ));
+
+        // The original plus a chain of 100 descendants is 101 evictions
+        let mut mempool = vec![original.clone()];
+        for i in 0..MAX_BIP125_REPLACEMENT_CANDIDATES {
+            let parent = mempool.last().unwrap().txid().0;
+            mempool.push(spend(parent, i as u8));
+        }
+        assert!(!rbf_descendant_ok(&replacement, &mempool));
+        mempool.pop();
+        assert!(rbf_descendant_ok(&replacement, &mempool));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_amount_btc_conversions() {
+        assert_eq!(Amount::from_btc(1.0), Ok(Amount::from_sat(100_000_000)));
+        // Neither 0.1 nor 0.29 is exact in binary; both still round to the
+        // intended satoshi count rather than truncating
+        assert_eq!(Amount::from_btc(0.1), Ok(Amount::from_sat(10_000_000)));
+        assert_eq!(Amount::from_btc(0.29), Ok(Amount::from_sat(29_000_000)));
+        assert_eq!(Amount::from_btc(0.000_000_01), Ok(Amount::from_sat(1)));
+        assert_eq!(Amount::from_btc(0.000_000_004), Ok(Amount::ZERO));
+        assert_eq!(Amount::from_btc(0.000_000_006), Ok(Amount::from_sat(1)));
+        assert_eq!(Amount::from_sat(123_456_789).to_btc(), 1.234_567_89);
+
+        assert_eq!(
+            Amount::from_btc(21_000_000.0),
+            Ok(Amount::from_sat(MAX_MONEY))
+        );
+        for bad in [21_000_000.000_000_1, -0.000_000_01, f64::NAN, f64::INFINITY] {
+            assert_eq!(Amount::from_btc(bad), Err(BitcoinError::InvalidFormat));
+        }
+    }
+
+    #[test]
+    fn test_amount_overflow_guards() {
+        assert_eq!(
+            Amount::from_sat_checked(MAX_MONEY),
+            Ok(Amount::from_sat(MAX_MONEY))
+        );
+        assert_eq!(
+            Amount::from_sat_checked(MAX_MONEY + 1),
+            Err(BitcoinError::InvalidFormat)
+        );
+
+        let one = Amount::from_sat(1);
+        assert_eq!(one.checked_add(one), Some(Amount::from_sat(2)));
+        assert_eq!(Amount::from_sat(u64::MAX).checked_add(one), None);
+        assert_eq!(one.checked_sub(one), Some(Amount::ZERO));
+        assert_eq!(Amount::ZERO.checked_sub(one), None);
+
+        // Outputs still serialize the value as 8 bytes LE, and JSON as a number
+        let output = TransactionOutput::new(1_000, dummy_p2wpkh(1));
+        assert_eq!(output.value, Amount::from_sat(1_000));
+        assert_eq!(&output.to_bytes()[..8], &1_000u64.to_le_bytes());
+        assert!(
+            serde_json::to_string(&output)
+                .unwrap()
+                .starts_with(r#"{"value":1000,"#)
+        );
+    }
+
+    #[test]
+    fn test_total_output_value_and_fee() {
+        let tx = block_170_tx();
+        assert_eq!(tx.total_output_value(), Some(5_000_000_000));
+        // Block 170 spent the 50 BTC genesis-era coinbase output in full
+        assert_eq!(tx.fee(&[5_000_000_000]), Some(0));
+        assert_eq!(tx.fee(&[5_000_010_000]), Some(10_000));
+
+        assert_eq!(tx.fee(&[4_999_999_999]), None);
+        assert_eq!(tx.fee(&[]), None);
+        assert_eq!(tx.fee(&[5_000_000_000, 1]), None);
+
+        let mut overflowing = tx.clone();
+        overflowing.outputs[0].value = Amount::from_sat(u64::MAX);
+        assert_eq!(overflowing.total_output_value(), None);
+        assert_eq!(overflowing.fee(&[u64::MAX]), None);
+    }
+
+    #[test]
+    fn test_sighash_preimage_hex_bip143() {
+        // Native P2WPKH example from BIP143: input 1 spends 6 BTC
+        let tx = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let script_code =
+            Script::new(hex::decode("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap());
+
+        let preimage = tx
+            .sighash_preimage_hex(1, &script_code, 600_000_000, SIGHASH_ALL)
+            .unwrap();
+        assert_eq!(
+            preimage,
+            concat!(
+                "01000000",
+                "96b827c8483d4e9b96712b6713a7b68d6e8003a781feba36c31143470b4efd37",
+                "52b0a642eea2fb7ae638c36f6252b6750293dbe574a806984b8e4d8548339a3b",
+                "ef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a01000000",
+                "1976a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac",
+                "0046c32300000000",
+                "ffffffff",
+                "863ef3e1a92afbfdb97f31ad0fc7683ee943e9abcf2501590ff8f6551f47e5e5",
+                "11000000",
+                "01000000",
+            )
+        );
+        assert_eq!(
+            hex::encode(hash256(&hex::decode(&preimage).unwrap())),
+            "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670"
+        );
+
+        // ANYONECANPAY|NONE zeroes all three commitment hashes
+        let zero = "00".repeat(32);
+        let none = tx
+            .sighash_preimage_hex(
+                1,
+                &script_code,
+                600_000_000,
+                SIGHASH_ANYONECANPAY | SIGHASH_NONE,
+            )
+            .unwrap();
+        assert_eq!(&none[8..136], format!("{}{}", zero, zero));
+        assert_eq!(&none[284..348], zero);
+        assert_eq!(
+            tx.sighash_preimage_hex(2, &script_code, 0, SIGHASH_ALL),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_meets_min_nonwitness_size() {
+        let with_script_len = |len: usize| {
+            BitcoinTransaction::new(
+                2,
+                vec![dummy_input(dummy_txid(1), 0)],
+                vec![TransactionOutput::new(0, Script::new(vec![0x51; len]))],
+                0,
+            )
+        };
+
+        // 60 bytes of framing around the output script
+        let tiny = with_script_len(0);
+        assert_eq!(tiny.to_bytes_legacy().len(), 60);
+        assert!(!tiny.meets_min_nonwitness_size());
+        assert!(!with_script_len(4).meets_min_nonwitness_size());
+        assert!(with_script_len(5).meets_min_nonwitness_size());
+
+        // Witness bytes don't count towards the minimum
+        let padded = tiny
+            .clone()
+            .with_witnesses(vec![Witness::new(vec![vec![0x01; 100]])]);
+        assert!(!padded.meets_min_nonwitness_size());
+        assert!(block_170_tx().meets_min_nonwitness_size());
+    }
+
+    #[test]
+    fn test_sequence_flags() {
+        let final_seq = Sequence::from_u32(0xFFFFFFFF);
+        assert!(final_seq.is_final());
+        assert!(!final_seq.is_rbf_signaling());
+        assert!(!final_seq.is_relative_timelock());
+
+        // Enables lock_time, but still not replaceable
+        let locktime_only = Sequence::from_u32(0xFFFFFFFE);
+        assert!(!locktime_only.is_final());
+        assert!(!locktime_only.is_rbf_signaling());
+        assert!(!locktime_only.is_relative_timelock());
+
+        assert!(Sequence::from_u32(SEQUENCE_RBF).is_rbf_signaling());
+        assert_eq!(
+            Sequence::from_u32(0xFFFFFFFD).to_relative_lock_blocks(),
+            None
+        );
+
+        // 144 blocks, then 10 units of 512 seconds
+        let blocks = Sequence::from_u32(144);
+        assert!(blocks.is_relative_timelock());
+        assert_eq!(blocks.to_relative_lock_blocks(), Some(144));
+        assert_eq!(blocks.to_relative_lock_time(), None);
+        let time = Sequence::from_u32((1 << 22) | 10);
+        assert_eq!(time.to_relative_lock_blocks(), None);
+        assert_eq!(time.to_relative_lock_time(), Some(5_120));
+
+        // Wire and JSON forms are the bare u32
+        let input = TransactionInput::new(
+            OutPoint::new(dummy_txid(1), 0),
+            Script::new(vec![]),
+            0xFFFFFFFE,
+        );
+        assert_eq!(&input.to_bytes()[37..], &[0xFE, 0xFF, 0xFF, 0xFF]);
+        assert!(
+            serde_json::to_string(&input)
+                .unwrap()
+                .ends_with(r#""sequence":4294967294}"#)
+        );
+    }
+
+    #[test]
+    fn test_lock_time_threshold() {
+        let last_height = LockTime::from_u32(499_999_999);
+        assert_eq!(last_height, LockTime::Blocks(499_999_999));
+        assert!(last_height.is_block_height());
+        assert!(!last_height.is_timestamp());
+
+        let first_time = LockTime::from_u32(LOCK_TIME_THRESHOLD);
+        assert_eq!(first_time, LockTime::Time(500_000_000));
+        assert!(first_time.is_timestamp());
+        assert!(!first_time.is_block_height());
+
+        for raw in [0, 499_999_999, 500_000_000, u32::MAX] {
+            assert_eq!(LockTime::from_u32(raw).to_u32(), raw);
+        }
+
+        // Wire and JSON forms are the bare u32
+        let tx = BitcoinTransaction::new(2, vec![], vec![], 500_000_000);
+        assert_eq!(tx.lock_time, first_time);
+        assert_eq!(&tx.to_bytes()[6..], &500_000_000u32.to_le_bytes());
+        let json = serde_json::to_string(&tx).unwrap();
+        assert!(json.ends_with(r#""lock_time":500000000}"#));
+        assert_eq!(
+            serde_json::from_str::<BitcoinTransaction>(&json).unwrap(),
+            tx
+        );
+    }
+
+    #[test]
+    fn test_output_commitment() {
+        let tx = block_170_tx();
+        let mut preimage = OutPoint::new(tx.txid().0, 1).to_bytes();
+        preimage.extend_from_slice(&tx.outputs[1].to_bytes());
+        assert_eq!(preimage.len(), 36 + 76);
+        assert_eq!(tx.output_commitment(1), Ok(hash256(&preimage)));
+
+        // Each output gets its own commitment
+        assert_ne!(tx.output_commitment(0), tx.output_commitment(1));
+        assert_eq!(tx.output_commitment(2), Err(BitcoinError::InvalidFormat));
+    }
+
+    #[test]
+    fn test_from_rest_bytes() {
+        // GET /rest/tx/f4184fc5...e9e16.bin returns exactly these 275 bytes
+        let payload = hex::decode(BLOCK_170_TX_HEX).unwrap();
+        let tx = BitcoinTransaction::from_rest_bytes(&payload).unwrap();
+        assert_eq!(tx.txid().to_string(), BLOCK_170_TXID);
+
+        let segwit = hex::decode(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert!(
+            BitcoinTransaction::from_rest_bytes(&segwit)
+                .unwrap()
+                .has_witness()
+        );
+
+        // A stray newline, as from the .hex endpoint, is not a .bin body
+        let mut with_newline = payload.clone();
+        with_newline.push(b'\n');
+        assert_eq!(
+            BitcoinTransaction::from_rest_bytes(&with_newline),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert!(BitcoinTransaction::from_rest_bytes(&payload[..100]).is_err());
+    }
+
+    #[test]
+    fn test_feerate_sat_per_wu() {
+        let tx = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        // 6.25 BTC and 6 BTC in, 1.1234 BTC and 2.2345 BTC out
+        let input_values = [625_000_000, 600_000_000];
+        let fee = tx.fee(&input_values).unwrap();
+
+        let per_wu = tx.feerate_sat_per_wu(&input_values).unwrap();
+        assert_eq!(per_wu, fee as f64 / 1_042.0);
+        let per_vb = fee as f64 / tx.vsize() as f64;
+        assert!((per_wu * 4.0 - per_vb).abs() / per_vb < 0.01);
+
+        assert_eq!(
+            tx.feerate_sat_per_wu(&[1, 1]),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_legacy_sighash() {
+        // Block 170 spends a P2PK coin paying the same key as its output 1;
+        // this is the digest Satoshi's signature in it commits to
+        let tx = block_170_tx();
+        let script_code = tx.outputs[1].script_pubkey.clone();
+        assert_eq!(
+            hex::encode(tx.legacy_sighash(0, &script_code, SIGHASH_ALL).unwrap()),
+            "7a05c6145f10101e9d6325494245adf1297d80f8f38d4d576d57cdba220bcb19"
+        );
+
+        // Each mode commits to something different
+        let all = tx.legacy_sighash(0, &script_code, SIGHASH_ALL).unwrap();
+        let none = tx.legacy_sighash(0, &script_code, SIGHASH_NONE).unwrap();
+        let single = tx.legacy_sighash(0, &script_code, SIGHASH_SINGLE).unwrap();
+        assert!(all != none && none != single && single != all);
+
+        // SINGLE without a matching output hashes to one
+        let mut fewer_outputs = tx.clone();
+        fewer_outputs.inputs.push(tx.inputs[0].clone());
+        fewer_outputs.outputs.truncate(1);
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        assert_eq!(
+            fewer_outputs.legacy_sighash(1, &script_code, SIGHASH_SINGLE),
+            Ok(one)
+        );
+
+        assert_eq!(
+            tx.legacy_sighash(1, &script_code, SIGHASH_ALL),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_segwit_sighash_bip143_vector() {
+        // Native P2WPKH example from BIP143, input 1 (6 BTC, SIGHASH_ALL)
+        let tx = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let script_code =
+            Script::new(hex::decode("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap());
+        let expected = "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670";
+
+        let sighash = tx
+            .segwit_sighash(1, &script_code, 600_000_000, SIGHASH_ALL)
+            .unwrap();
+        assert_eq!(hex::encode(sighash), expected);
+
+        // A shared cache gives the same digests as one-off calls
+        let cache = SighashCache::new(&tx);
+        assert_eq!(
+            cache.segwit_sighash(1, &script_code, 600_000_000, SIGHASH_ALL),
+            Ok(sighash)
+        );
+        for sighash_type in [
+            SIGHASH_NONE,
+            SIGHASH_SINGLE,
+            SIGHASH_ALL | SIGHASH_ANYONECANPAY,
+        ] {
+            assert_eq!(
+                cache.segwit_sighash(0, &script_code, 625_000_000, sighash_type),
+                tx.segwit_sighash(0, &script_code, 625_000_000, sighash_type)
+            );
+        }
+
+        // The amount is committed to
+        assert_ne!(
+            tx.segwit_sighash(1, &script_code, 600_000_001, SIGHASH_ALL),
+            Ok(sighash)
+        );
+        assert_eq!(
+            cache.segwit_sighash(2, &script_code, 0, SIGHASH_ALL),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_taproot_key_spend_sig() {
+        let default_sig = vec![0xAA; 64];
+        let mut single_sig = vec![0xBB; 64];
+        single_sig.push(0x83);
+
+        let witness = Witness::new(vec![default_sig.clone()]);
+        assert_eq!(
+            witness.taproot_key_spend_sig(),
+            Some(default_sig.as_slice())
+        );
+        let witness = Witness::new(vec![single_sig.clone()]);
+        assert_eq!(witness.taproot_key_spend_sig(), Some(single_sig.as_slice()));
+
+        // An annex is ignored
+        let witness = Witness::new(vec![default_sig.clone(), vec![0x50, 0x01]]);
+        assert_eq!(
+            witness.taproot_key_spend_sig(),
+            Some(default_sig.as_slice())
+        );
+
+        // Script-path spends, P2WPKH, wrong lengths and an explicit 0x00 type
+        let mut explicit_default = default_sig.clone();
+        explicit_default.push(0x00);
+        for items in [
+            vec![default_sig.clone(), vec![0x51], vec![0xC0; 33]],
+            vec![vec![0x30; 71], vec![0x02; 33]],
+            vec![vec![0xAA; 63]],
+            vec![explicit_default],
+            vec![],
+        ] {
+            assert_eq!(Witness::new(items).taproot_key_spend_sig(), None);
+        }
+    }
+
+    #[test]
+    fn test_marker_flag_weight() {
+        let legacy = block_170_tx();
+        assert_eq!(legacy.marker_flag_weight(), 0);
+
+        let witness = Witness::new(vec![vec![0x30; 71], vec![0x02; 33]]);
+        let segwit = legacy.clone().with_witnesses(vec![witness.clone()]);
+        assert_eq!(segwit.marker_flag_weight(), 2);
+
+        // Segwit weight over the legacy weight is the marker/flag plus the
+        // witness stacks, all at 1 WU per byte
+        assert_eq!(
+            segwit.weight() - legacy.weight(),
+            segwit.marker_flag_weight() + witness.to_bytes().len()
+        );
+
+        // Empty witnesses alone don't switch to the segwit layout
+        let empty = legacy.with_witnesses(vec![Witness::default()]);
+        assert_eq!(empty.marker_flag_weight(), 0);
+    }
+
+    #[test]
+    fn test_consensus_decode_script_spanning_read_chunks() {
+        // Payloads are read in fixed-size chunks; one several chunks long
+        // must come back whole, and a short one must report what arrived
+        let script = Script::new(vec![0x5A; 10_000]);
+        let mut stream = Vec::new();
+        script.consensus_encode(&mut stream).unwrap();
+        assert_eq!(Script::consensus_decode(&mut &stream[..]).unwrap(), script);
+
+        stream.truncate(stream.len() - 1);
+        assert_eq!(
+            Script::consensus_decode(&mut &stream[..]),
+            Err(BitcoinError::InsufficientBytes {
+                needed: 10_000,
+                got: 9_999
+            })
+        );
+    }
+
+    #[test]
+    fn test_crate_io_traits() {
+        // A caller's own sink, written against the crate's `io::Write`; it
+        // must keep compiling whether or not `std` is enabled
+        struct Counter(usize);
+        impl io::Write for Counter {
+            fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+                self.0 += buf.len();
+                Ok(())
+            }
+        }
+
+        let tx = block_170_tx();
+        let mut counter = Counter(0);
+        assert_eq!(tx.consensus_encode(&mut counter), Ok(275));
+        assert_eq!(counter.0, 275);
+        assert_eq!(tx.consensus_encode(&mut io::sink()), Ok(275));
+
+        // Back-to-back records out of a plain byte slice
+        let mut stream = Vec::new();
+        tx.consensus_encode(&mut stream).unwrap();
+        tx.consensus_encode(&mut stream).unwrap();
+        let mut reader = &stream[..];
+        assert_eq!(
+            BitcoinTransaction::consensus_decode(&mut reader),
+            Ok(tx.clone())
+        );
+        assert_eq!(BitcoinTransaction::consensus_decode(&mut reader), Ok(tx));
+        assert!(reader.is_empty());
+
+        assert_eq!(
+            CompactSize::consensus_decode(&mut &[0xFD, 0x10, 0x00][..]),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_rejects_superfluous_witness_record() {
+        // Block 170's single input re-encoded with the marker/flag but an
+        // empty witness; to_bytes would write it back without them
+        let legacy = block_170_tx().to_bytes();
+        let mut bytes = legacy[..4].to_vec();
+        bytes.extend_from_slice(&[0x00, 0x01]);
+        bytes.extend_from_slice(&legacy[4..legacy.len() - 4]);
+        bytes.push(0x00);
+        bytes.extend_from_slice(&legacy[legacy.len() - 4..]);
+
+        assert_eq!(
+            BitcoinTransaction::deserialize(&bytes),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert_eq!(
+            BitcoinTransaction::consensus_decode(&mut &bytes[..]),
+            Err(BitcoinError::InvalidFormat)
+        );
+
+        // The same layout with a non-empty witness is fine
+        let len = bytes.len();
+        bytes[len - 5] = 0x01;
+        bytes.splice(len - 4..len - 4, [0x01, 0xAB]);
+        let tx = BitcoinTransaction::deserialize(&bytes).unwrap();
+        assert_eq!(tx.to_bytes(), bytes);
+    }
 }